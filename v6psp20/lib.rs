@@ -4,7 +4,9 @@
 mod Token {
     use ink::prelude::vec::Vec;
     use ink::storage::Mapping;
-    use ink::primitives::H160;
+    use ink::primitives::{H160, H256, U256};
+    use ink::env::call::{build_call, ExecutionInput, Selector};
+    use ink::env::DefaultEnvironment;
 
     /// Event emitted when a token transfer occurs
     #[ink(event)]
@@ -16,6 +18,17 @@ mod Token {
         value: Balance,
     }
 
+    /// Event emitted alongside every Transfer, tagging it with a CATEGORY_* constant
+    #[ink(event)]
+    pub struct TransferCategorized {
+        #[ink(topic)]
+        from: Option<H160>,
+        #[ink(topic)]
+        to: Option<H160>,
+        value: Balance,
+        category: u8,
+    }
+
     /// Event emitted when an approval occurs
     #[ink(event)]
     pub struct Approval {
@@ -62,6 +75,87 @@ mod Token {
         account: H160,
     }
 
+    /// Event emitted when a transfer_from drops an allowance below the low allowance threshold
+    #[ink(event)]
+    pub struct LowAllowance {
+        #[ink(topic)]
+        owner: H160,
+        #[ink(topic)]
+        spender: H160,
+        remaining: Balance,
+    }
+
+    /// Event emitted when ownership moves to a new account
+    #[ink(event)]
+    pub struct OwnershipTransferred {
+        #[ink(topic)]
+        previous: H160,
+        #[ink(topic)]
+        new: H160,
+    }
+
+    /// Event emitted when the owner freezes an account's outgoing transfers
+    #[ink(event)]
+    pub struct AccountFrozen {
+        #[ink(topic)]
+        account: H160,
+    }
+
+    /// Event emitted when the owner lifts a freeze
+    #[ink(event)]
+    pub struct AccountUnfrozen {
+        #[ink(topic)]
+        account: H160,
+    }
+
+    /// Event emitted when an account is granted the minter role
+    #[ink(event)]
+    pub struct MinterAdded {
+        #[ink(topic)]
+        account: H160,
+    }
+
+    /// Event emitted when an account loses the minter role
+    #[ink(event)]
+    pub struct MinterRemoved {
+        #[ink(topic)]
+        account: H160,
+    }
+
+    /// Event emitted when the owner rebases the total supply
+    #[ink(event)]
+    pub struct Rebased {
+        old_total_supply: Balance,
+        new_total_supply: Balance,
+    }
+
+    /// Event emitted when transfer_with_native_fee pays the native fee to the fee collector
+    #[ink(event)]
+    pub struct NativeFeePaid {
+        #[ink(topic)]
+        payer: H160,
+        #[ink(topic)]
+        collector: H160,
+        amount: U256,
+    }
+
+    /// Event emitted when a new fee collector is queued
+    #[ink(event)]
+    pub struct FeeCollectorQueued {
+        #[ink(topic)]
+        collector: H160,
+        executable_at: u64,
+    }
+
+    /// Event emitted when a queued fee collector takes effect
+    #[ink(event)]
+    pub struct FeeCollectorChanged {
+        #[ink(topic)]
+        old_collector: H160,
+        #[ink(topic)]
+        new_collector: H160,
+    }
+
     #[derive(Debug, PartialEq, Eq)]
     #[ink::scale_derive(Encode, Decode, TypeInfo)]
     pub enum Error {
@@ -70,556 +164,4242 @@ mod Token {
         Paused,
         Blacklisted,
         Unauthorized,
+        BelowMinimum,
+        ApprovalTooLarge,
+        RecipientCapExceeded,
+        DeniedContract,
+        AccountFrozen,
+        NotFrozen,
+        UnfreezeNotReady,
+        InsufficientFee,
+        NativeTransferFailed,
+        CapExceeded,
+        CapBelowSupply,
+        ReceiveCapExceeded,
+        Expired,
+        NoFeeCollectorQueued,
+        FeeCollectorNotReady,
+        ZeroAmount,
+        HolderLimitReached,
+        Overflow,
+        ZeroAddress,
+        InvalidRebase,
+        TransferRestricted,
+        InvalidTag,
+        StreamNotFound,
+        CallbackFailed,
+        BatchTooLarge,
+        RolesStillAssigned,
+        RestrictionsActive,
     }
 
     pub type Result<T> = core::result::Result<T, Error>;
 
+    /// Snapshot of the Token's admin parameters
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    pub struct Config {
+        pub owner: H160,
+        pub pending_owner: Option<H160>,
+        pub operator: Option<H160>,
+        pub paused: bool,
+        pub treasury: H160,
+        pub decimals: u8,
+        pub min_transfer_whole: Balance,
+        pub max_supply: Balance,
+        pub min_approval: Balance,
+        pub max_approval: Option<Balance>,
+        pub low_allowance_threshold: Balance,
+        pub native_fee: U256,
+        pub fee_collector: H160,
+        pub max_holders: Option<u32>,
+        pub transfer_validator: Option<H160>,
+    }
+
+    /// Tokens escrowed by a sender and released to a recipient linearly over time.
+    /// The escrow is held as shares, so it rebases like any other holding.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct Stream {
+        pub sender: H160,
+        pub recipient: H160,
+        /// Tokens per second at creation
+        pub rate_per_sec: Balance,
+        /// Start timestamp in milliseconds
+        pub start: u64,
+        /// Duration in seconds; 0 once cancelled with a blocked payout, leaving every share claimable
+        pub duration: u64,
+        /// Shares escrowed for the whole stream
+        pub shares: Balance,
+        /// Shares the recipient has already claimed
+        pub withdrawn_shares: Balance,
+    }
+
+    /// Tag bit standing for untagged accounts in allowed_tag_transitions; it cannot be assigned to an account
+    pub const UNTAGGED_TAG: u8 = 31;
+
+    /// Transfer category for ordinary user transfers
+    pub const CATEGORY_USER: u8 = 0;
+    /// Transfer category for token fees; a transfer that pays the native fee is a user transfer
+    /// and the fee itself is reported by NativeFeePaid
+    pub const CATEGORY_FEE: u8 = 1;
+    /// Transfer category for newly minted tokens
+    pub const CATEGORY_MINT: u8 = 2;
+    /// Transfer category for burned tokens
+    pub const CATEGORY_BURN: u8 = 3;
+    /// Transfer category for transfers made by the contract's admins, e.g. out of the treasury
+    pub const CATEGORY_SYSTEM: u8 = 4;
+
+    /// Fixed-point scale of shares_per_token, i.e. its value before any rebase
+    pub const SHARE_UNIT: Balance = 1_000_000_000_000_000_000;
+
+    /// Maximum number of entries accepted by batch_approve
+    pub const MAX_BATCH_APPROVALS: usize = 50;
+
+    /// Delay (milliseconds) between requesting and performing a self-unfreeze
+    pub const SELF_UNFREEZE_DELAY: u64 = 24 * 60 * 60 * 1000;
+
+    /// Delay (milliseconds) between queuing and executing a fee collector change
+    pub const FEE_COLLECTOR_DELAY: u64 = 2 * 24 * 60 * 60 * 1000;
+
+    /// Code hash reported for accounts without code (keccak256 of empty input)
+    const EMPTY_CODE_HASH: [u8; 32] = [
+        0xc5, 0xd2, 0x46, 0x01, 0x86, 0xf7, 0x23, 0x3c, 0x92, 0x7e, 0x7d, 0xb2, 0xdc, 0xc7, 0x03, 0xc0,
+        0xe5, 0x00, 0xb6, 0x53, 0xca, 0x82, 0x27, 0x3b, 0x7b, 0xfa, 0xd8, 0x04, 0x5d, 0x85, 0xa4, 0x70,
+    ];
+
     #[ink(storage)]
     pub struct Token {
         /// Total token supply
         total_supply: Balance,
-        /// Mapping from owner to balance
+        /// Maximum total supply (0 means uncapped)
+        max_supply: Balance,
+        /// Mapping from owner to their shares; balances are shares converted at shares_per_token
         balances: Mapping<H160, Balance>,
+        /// Sum of all shares
+        total_shares: Balance,
+        /// Shares per whole token scaled by SHARE_UNIT, adjusted by rebase
+        shares_per_token: U256,
         /// Mapping from (owner, spender) to allowance
         allowances: Mapping<(H160, H160), Balance>,
+        /// Mapping from owner to the spenders holding a nonzero allowance
+        spenders: Mapping<H160, Vec<H160>>,
         /// Contract owner
         owner: H160,
+        /// Proposed owner waiting to accept ownership
+        pending_owner: Option<H160>,
+        /// Secondary admin allowed to pause, unpause and blacklist
+        operator: Option<H160>,
         /// Paused state
         paused: bool,
+        /// Message selectors blocked individually while the rest of the token stays open
+        blocked_selectors: Mapping<[u8; 4], bool>,
+        /// Number of blocked message selectors
+        blocked_selector_count: u32,
         /// Blacklisted addresses
         blacklist: Mapping<H160, bool>,
+        /// Account holding the treasury funds
+        treasury: H160,
+        /// Proposed treasury account waiting to accept the role
+        pending_treasury: Option<H160>,
+        /// Accounts allowed to move funds out of the treasury
+        finance_admins: Mapping<H160, bool>,
+        /// Number of accounts holding the finance admin role
+        finance_admin_count: u32,
+        /// Accounts allowed to mint besides the owner
+        minters: Mapping<H160, bool>,
+        /// Number of accounts holding the minter role
+        minter_count: u32,
+        /// Accounts that froze their own outgoing transfers
+        self_frozen: Mapping<H160, bool>,
+        /// Accounts whose outgoing transfers the owner froze; they can still receive
+        frozen: Mapping<H160, bool>,
+        /// Number of accounts the owner froze
+        frozen_count: u32,
+        /// Mapping from self-frozen account to the timestamp its unfreeze becomes available
+        unfreeze_requests: Mapping<H160, u64>,
+        /// Contracts that cannot receive transfers
+        contract_denylist: Mapping<H160, bool>,
+        /// Mapping from recipient to (cap, window in milliseconds) on tokens received per window
+        received_caps: Mapping<H160, (Balance, u64)>,
+        /// Mapping from recipient to (amount received, window start) for the current window
+        received_in_window: Mapping<H160, (Balance, u64)>,
+        /// Mapping from account to its jurisdiction tag bitmask (untagged accounts are unrestricted)
+        account_tags: Mapping<H160, u32>,
+        /// Mapping from a sender tag bit to the bitmask of recipient tags it may transfer to
+        allowed_tag_transitions: Mapping<u8, u32>,
+        /// Contract asked to approve every transfer through `validate_transfer`, if set
+        transfer_validator: Option<H160>,
+        /// Mapping from stream id to its escrowed payment stream
+        streams: Mapping<u32, Stream>,
+        /// Id of the next payment stream
+        next_stream_id: u32,
+        /// Native currency fee charged by transfer_with_native_fee
+        native_fee: U256,
+        /// Account receiving native fees (defaults to the deployer)
+        fee_collector: H160,
+        /// Queued fee collector and the timestamp it becomes executable
+        pending_fee_collector: Option<(H160, u64)>,
+        /// Smallest nonzero allowance that can be approved
+        min_approval: Balance,
+        /// Largest allowance that can be approved, if capped
+        max_approval: Option<Balance>,
+        /// Allowance level below which transfer_from emits LowAllowance (0 disables)
+        low_allowance_threshold: Balance,
+        /// Token name (empty when unset)
+        name: Vec<u8>,
+        /// Token symbol (empty when unset)
+        symbol: Vec<u8>,
+        /// Number of decimals used to scale whole-token amounts
+        decimals: u8,
+        /// Minimum transfer in whole tokens (0 disables)
+        min_transfer_whole: Balance,
+        /// Number of accounts holding a nonzero balance
+        holder_count: u32,
+        /// Maximum number of holders, if capped
+        max_holders: Option<u32>,
+        /// Accounts whose balances are excluded from the circulating supply
+        non_circulating: Vec<H160>,
     }
 
     impl Token {
         /// Constructor that initializes the token with initial supply
         #[ink(constructor)]
         pub fn new(initial_supply: Balance) -> Self {
+            Self::new_capped(initial_supply, 0)
+        }
+
+        /// Constructor with a hard cap on total supply (0 means uncapped); the initial supply must fit under it
+        #[ink(constructor)]
+        pub fn new_capped(initial_supply: Balance, max_supply: Balance) -> Self {
+            assert!(
+                max_supply == 0 || initial_supply <= max_supply,
+                "initial supply exceeds max supply"
+            );
+
             let caller = Self::env().caller();
             let mut balances = Mapping::default();
             balances.insert(caller, &initial_supply);
 
-            // Self::env().emit_event(Transfer {
-            //     from: None,
-            //     to: Some(caller),
-            //     value: initial_supply,
-            // });
+            Self::emit_transfer(None, Some(caller), initial_supply, CATEGORY_MINT);
 
             Self {
                 total_supply: initial_supply,
+                max_supply,
                 balances,
+                total_shares: initial_supply,
+                shares_per_token: U256::from(SHARE_UNIT),
                 allowances: Mapping::default(),
+                spenders: Mapping::default(),
                 owner: caller,
+                pending_owner: None,
+                operator: None,
                 paused: false,
+                blocked_selectors: Mapping::default(),
+                blocked_selector_count: 0,
                 blacklist: Mapping::default(),
+                treasury: caller,
+                pending_treasury: None,
+                finance_admins: Mapping::default(),
+                finance_admin_count: 0,
+                minters: Mapping::default(),
+                minter_count: 0,
+                self_frozen: Mapping::default(),
+                frozen: Mapping::default(),
+                frozen_count: 0,
+                unfreeze_requests: Mapping::default(),
+                contract_denylist: Mapping::default(),
+                received_caps: Mapping::default(),
+                received_in_window: Mapping::default(),
+                account_tags: Mapping::default(),
+                allowed_tag_transitions: Mapping::default(),
+                transfer_validator: None,
+                streams: Mapping::default(),
+                next_stream_id: 0,
+                native_fee: U256::zero(),
+                fee_collector: caller,
+                pending_fee_collector: None,
+                min_approval: 0,
+                max_approval: None,
+                low_allowance_threshold: 0,
+                name: Vec::new(),
+                symbol: Vec::new(),
+                decimals: 18,
+                min_transfer_whole: 0,
+                holder_count: if initial_supply > 0 { 1 } else { 0 },
+                max_holders: None,
+                non_circulating: Vec::new(),
             }
         }
 
+        /// Constructor that also sets the token's name, symbol and decimals
+        #[ink(constructor)]
+        pub fn new_with_metadata(initial_supply: Balance, name: Vec<u8>, symbol: Vec<u8>, decimals: u8) -> Self {
+            let mut token = Self::new(initial_supply);
+            token.name = name;
+            token.symbol = symbol;
+            token.decimals = decimals;
+            token
+        }
+
         /// Default constructor with 1,000,000 initial supply
         #[ink(constructor)]
         pub fn default() -> Self {
             Self::new(1000000)
         }
 
-        /// Returns the total token supply
+        /// Returns the token name
         #[ink(message)]
-        pub fn total_supply(&self) -> Balance {
-            self.total_supply
+        pub fn token_name(&self) -> Vec<u8> {
+            self.name.clone()
         }
 
-        /// Returns the balance of the given account
+        /// Returns the token symbol
         #[ink(message)]
-        pub fn balance_of(&self, owner: H160) -> Balance {
-            self.balances.get(owner).unwrap_or(0)
+        pub fn token_symbol(&self) -> Vec<u8> {
+            self.symbol.clone()
         }
 
-        /// Returns the allowance for a spender approved by an owner
+        /// Returns the number of decimals
         #[ink(message)]
-        pub fn allowance(&self, owner: H160, spender: H160) -> Balance {
-            self.allowances.get((owner, spender)).unwrap_or(0)
+        pub fn token_decimals(&self) -> u8 {
+            self.decimals
         }
 
-        /// Transfers tokens from the caller to another account
+        /// Returns the total token supply
         #[ink(message)]
-        pub fn transfer(&mut self, to: H160, value: Balance) -> Result<()> {
-            let from = self.env().caller();
-            self.transfer_from_to(&from, &to, value)?;
-            Ok(())
+        pub fn total_supply(&self) -> Balance {
+            self.total_supply
         }
 
-        /// Approves a spender to spend tokens on behalf of the caller
+        /// Returns the total supply minus the balances of non-circulating accounts
         #[ink(message)]
-        pub fn approve(&mut self, spender: H160, value: Balance) -> Result<()> {
-            let owner = self.env().caller();
-            self.allowances.insert((owner, spender), &value);
-
-            // self.env().emit_event(Approval {
-            //     owner,
-            //     spender,
-            //     value,
-            // });
-
-            Ok(())
+        pub fn circulating_supply(&self) -> Balance {
+            let locked = self
+                .non_circulating
+                .iter()
+                .fold(0, |total: Balance, account| total.saturating_add(self.balance_of(*account)));
+            self.total_supply.saturating_sub(locked)
         }
 
-        /// Transfers tokens from one account to another using allowance
+        /// Excludes an account's balance from the circulating supply, e.g. the treasury (only owner)
         #[ink(message)]
-        pub fn transfer_from(
-            &mut self,
-            from: H160,
-            to: H160,
-            value: Balance,
-        ) -> Result<()> {
+        pub fn add_non_circulating(&mut self, account: H160) -> Result<()> {
             let caller = self.env().caller();
-            let allowance = self.allowance(from, caller);
-
-            if allowance < value {
-                return Err(Error::InsufficientAllowance);
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
             }
 
-            self.transfer_from_to(&from, &to, value)?;
-            self.allowances.insert((from, caller), &allowance.saturating_sub(value));
-
+            if !self.non_circulating.contains(&account) {
+                self.non_circulating.push(account);
+            }
             Ok(())
         }
 
-        /// Mints new tokens to the caller's balance
+        /// Counts an account's balance as circulating again (only owner)
         #[ink(message)]
-        pub fn mint(&mut self, value: Balance) -> Result<()> {
+        pub fn remove_non_circulating(&mut self, account: H160) -> Result<()> {
             let caller = self.env().caller();
-            let balance = self.balance_of(caller);
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
+            }
 
-            self.balances.insert(caller, &balance.saturating_add(value));
-            self.total_supply = self.total_supply.saturating_add(value);
+            self.non_circulating.retain(|listed| *listed != account);
+            Ok(())
+        }
 
-            // self.env().emit_event(Transfer {
-            //     from: None,
-            //     to: Some(caller),
-            //     value,
-            // });
+        /// Returns the accounts excluded from the circulating supply
+        #[ink(message)]
+        pub fn non_circulating_accounts(&self) -> Vec<H160> {
+            self.non_circulating.clone()
+        }
 
-            Ok(())
+        /// Returns the maximum total supply (0 means uncapped)
+        #[ink(message)]
+        pub fn max_supply(&self) -> Balance {
+            self.max_supply
         }
 
-        /// Burns tokens from the caller's balance
+        /// Sets the maximum total supply, 0 to uncap; it cannot be below the current supply (only owner)
         #[ink(message)]
-        pub fn burn(&mut self, value: Balance) -> Result<()> {
+        pub fn set_cap(&mut self, cap: Balance) -> Result<()> {
             let caller = self.env().caller();
-            let balance = self.balance_of(caller);
-
-            if balance < value {
-                return Err(Error::InsufficientBalance);
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
             }
 
-            self.balances.insert(caller, &balance.saturating_sub(value));
-            self.total_supply = self.total_supply.saturating_sub(value);
-
-            // self.env().emit_event(Burn {
-            //     from: caller,
-            //     value,
-            // });
-
-            // self.env().emit_event(Transfer {
-            //     from: Some(caller),
-            //     to: None,
-            //     value,
-            // });
+            if cap != 0 && cap < self.total_supply {
+                return Err(Error::CapBelowSupply);
+            }
 
+            self.max_supply = cap;
             Ok(())
         }
 
-        /// Increases allowance for a spender
+        /// Returns the balance of the given account
         #[ink(message)]
-        pub fn increase_allowance(&mut self, spender: H160, delta_value: Balance) -> Result<()> {
-            let owner = self.env().caller();
-            let current_allowance = self.allowance(owner, spender);
-            self.allowances.insert((owner, spender), &current_allowance.saturating_add(delta_value));
-            Ok(())
+        pub fn balance_of(&self, owner: H160) -> Balance {
+            self.to_tokens(self.shares_of(owner))
         }
 
-        /// Decreases allowance for a spender
+        /// Returns the shares held by the given account
         #[ink(message)]
-        pub fn decrease_allowance(&mut self, spender: H160, delta_value: Balance) -> Result<()> {
-            let owner = self.env().caller();
-            let current_allowance = self.allowance(owner, spender);
-
-            if current_allowance < delta_value {
-                return Err(Error::InsufficientAllowance);
-            }
+        pub fn shares_of(&self, owner: H160) -> Balance {
+            self.balances.get(owner).unwrap_or(0)
+        }
 
-            self.allowances.insert((owner, spender), &current_allowance.saturating_sub(delta_value));
-            Ok(())
+        /// Returns the current shares per token, scaled by SHARE_UNIT
+        #[ink(message)]
+        pub fn shares_per_token(&self) -> U256 {
+            self.shares_per_token
         }
 
-        /// Pauses the contract (only owner)
+        /// Scales every balance so the total supply becomes `new_total_supply` (only owner)
         #[ink(message)]
-        pub fn pause(&mut self) -> Result<()> {
+        pub fn rebase(&mut self, new_total_supply: Balance) -> Result<()> {
             let caller = self.env().caller();
             if caller != self.owner {
                 return Err(Error::Unauthorized);
             }
 
-            self.paused = true;
+            if new_total_supply == 0 || self.total_shares == 0 {
+                return Err(Error::InvalidRebase);
+            }
 
-            // self.env().emit_event(Paused { by: caller });
+            if self.max_supply != 0 && new_total_supply > self.max_supply {
+                return Err(Error::CapExceeded);
+            }
+
+            let shares_per_token =
+                U256::from(self.total_shares) * U256::from(SHARE_UNIT) / U256::from(new_total_supply);
+            if shares_per_token.is_zero() {
+                return Err(Error::InvalidRebase);
+            }
 
+            let old_total_supply = self.total_supply;
+            self.shares_per_token = shares_per_token;
+            // The supply is whatever the shares are worth at the new rate, which may round above the target
+            self.total_supply = self.to_tokens(self.total_shares);
+
+            self.env().emit_event(Rebased {
+                old_total_supply,
+                new_total_supply: self.total_supply,
+            });
             Ok(())
         }
 
-        /// Unpauses the contract (only owner)
+        /// Returns how much the account can move out right now (0 while paused, blacklisted or frozen)
         #[ink(message)]
-        pub fn unpause(&mut self) -> Result<()> {
-            let caller = self.env().caller();
-            if caller != self.owner {
-                return Err(Error::Unauthorized);
+        pub fn transferable_balance(&self, account: H160) -> Balance {
+            if self.paused
+                || self.is_blacklisted(account)
+                || self.is_self_frozen(account)
+                || self.is_frozen(account)
+            {
+                return 0;
             }
+            self.balance_of(account)
+        }
 
-            self.paused = false;
+        /// Returns the allowance for a spender approved by an owner
+        #[ink(message)]
+        pub fn allowance(&self, owner: H160, spender: H160) -> Balance {
+            self.allowances.get((owner, spender)).unwrap_or(0)
+        }
 
-            // self.env().emit_event(Unpaused { by: caller });
+        /// Returns the spenders holding a nonzero allowance from an owner
+        #[ink(message)]
+        pub fn spenders_of(&self, owner: H160) -> Vec<H160> {
+            self.spenders.get(owner).unwrap_or_default()
+        }
 
-            Ok(())
+        /// Returns the sum of all allowances granted by an owner, i.e. their maximum potential outflow
+        #[ink(message)]
+        pub fn total_approved_out(&self, owner: H160) -> Balance {
+            self.spenders_of(owner)
+                .into_iter()
+                .fold(0, |total: Balance, spender| total.saturating_add(self.allowance(owner, spender)))
         }
 
-        /// Returns whether the contract is paused
+        /// Returns how many spenders hold a nonzero allowance from an owner and the sum of those allowances
         #[ink(message)]
-        pub fn is_paused(&self) -> bool {
-            self.paused
+        pub fn allowance_summary(&self, owner: H160) -> (u32, Balance) {
+            let spenders = self.spenders_of(owner);
+            let count = u32::try_from(spenders.len()).unwrap_or(u32::MAX);
+            (count, self.total_approved_out(owner))
         }
 
-        /// Adds an address to the blacklist (only owner)
+        /// Transfers tokens from the caller to another account
         #[ink(message)]
-        pub fn blacklist_address(&mut self, account: H160) -> Result<()> {
-            let caller = self.env().caller();
-            if caller != self.owner {
-                return Err(Error::Unauthorized);
+        pub fn transfer(&mut self, to: H160, value: Balance) -> Result<()> {
+            self.ensure_selector_open(ink::selector_bytes!("transfer"))?;
+            let from = self.env().caller();
+            self.transfer_from_to(&from, &to, value, CATEGORY_USER)?;
+            Ok(())
+        }
+
+        /// Transfers tokens unless the recipient's resulting balance would exceed `recipient_max`
+        #[ink(message)]
+        pub fn transfer_capped(&mut self, to: H160, value: Balance, recipient_max: Balance) -> Result<()> {
+            self.ensure_selector_open(ink::selector_bytes!("transfer_capped"))?;
+            if self.balance_of(to).saturating_add(value) > recipient_max {
+                return Err(Error::RecipientCapExceeded);
             }
 
-            self.blacklist.insert(account, &true);
+            self.transfer(to, value)
+        }
 
-            // self.env().emit_event(Blacklisted { account });
+        /// Transfers tokens only if the current block timestamp is not past `deadline` (milliseconds)
+        #[ink(message)]
+        pub fn transfer_before(&mut self, to: H160, value: Balance, deadline: u64) -> Result<()> {
+            self.ensure_selector_open(ink::selector_bytes!("transfer_before"))?;
+            if self.env().block_timestamp() > deadline {
+                return Err(Error::Expired);
+            }
 
-            Ok(())
+            self.transfer(to, value)
         }
 
-        /// Removes an address from the blacklist (only owner)
+        /// Transfers tokens and, if `to` is a contract, calls its `on_token_transfer(from, value, data)`;
+        /// a failed callback reverts the transfer
         #[ink(message)]
-        pub fn remove_from_blacklist(&mut self, account: H160) -> Result<()> {
+        pub fn transfer_and_call(&mut self, to: H160, value: Balance, data: Vec<u8>) -> Result<()> {
+            self.ensure_selector_open(ink::selector_bytes!("transfer_and_call"))?;
+            let from = self.env().caller();
+            self.transfer(to, value)?;
+
+            // Like ERC677, plain accounts receive the tokens without a callback
+            if !self.is_contract(to) {
+                return Ok(());
+            }
+
+            build_call::<DefaultEnvironment>()
+                .call(to)
+                .transferred_value(U256::zero())
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink::selector_bytes!("on_token_transfer")))
+                        .push_arg(from)
+                        .push_arg(value)
+                        .push_arg(data)
+                )
+                .returns::<()>()
+                .try_invoke()
+                .map_err(|_| Error::CallbackFailed)?
+                .map_err(|_| Error::CallbackFailed)
+        }
+
+        /// Transfers tokens while paying the native fee to the fee collector; any excess value is refunded
+        #[ink(message, payable)]
+        pub fn transfer_with_native_fee(&mut self, to: H160, value: Balance) -> Result<()> {
+            self.ensure_selector_open(ink::selector_bytes!("transfer_with_native_fee"))?;
             let caller = self.env().caller();
-            if caller != self.owner {
-                return Err(Error::Unauthorized);
+            let paid = self.env().transferred_value();
+            let fee = self.native_fee;
+
+            if paid < fee {
+                return Err(Error::InsufficientFee);
             }
 
-            self.blacklist.remove(account);
+            self.transfer_from_to(&caller, &to, value, CATEGORY_USER)?;
 
-            // self.env().emit_event(RemovedFromBlacklist { account });
+            if fee > U256::zero() {
+                self.env()
+                    .transfer(self.fee_collector, fee)
+                    .map_err(|_| Error::NativeTransferFailed)?;
+
+                self.env().emit_event(NativeFeePaid {
+                    payer: caller,
+                    collector: self.fee_collector,
+                    amount: fee,
+                });
+            }
+
+            let excess = paid.saturating_sub(fee);
+            if excess > U256::zero() {
+                self.env()
+                    .transfer(caller, excess)
+                    .map_err(|_| Error::NativeTransferFailed)?;
+            }
 
             Ok(())
         }
 
-        /// Checks if an address is blacklisted
+        /// Approves a spender to spend tokens on behalf of the caller, returning the previous allowance
         #[ink(message)]
-        pub fn is_blacklisted(&self, account: H160) -> bool {
-            self.blacklist.get(account).unwrap_or(false)
+        pub fn approve(&mut self, spender: H160, value: Balance) -> Result<Balance> {
+            self.ensure_selector_open(ink::selector_bytes!("approve"))?;
+            let owner = self.env().caller();
+            self.ensure_valid_approval(value)?;
+            let previous = self.allowance(owner, spender);
+            self.set_allowance(owner, spender, value);
+
+            self.env().emit_event(Approval {
+                owner,
+                spender,
+                value,
+            });
+
+            Ok(previous)
         }
 
-        /// Batch transfer to multiple recipients
+        /// Approves up to `MAX_BATCH_APPROVALS` spenders in one call; a repeated spender ends up with its last value
         #[ink(message)]
-        pub fn batch_transfer(&mut self, recipients: Vec<(H160, Balance)>) -> Result<()> {
-            for (to, value) in recipients {
-                self.transfer(to, value)?;
+        pub fn batch_approve(&mut self, approvals: Vec<(H160, Balance)>) -> Result<()> {
+            self.ensure_selector_open(ink::selector_bytes!("batch_approve"))?;
+            if approvals.len() > MAX_BATCH_APPROVALS {
+                return Err(Error::BatchTooLarge);
+            }
+
+            // Validate every entry first so an invalid one leaves all allowances untouched
+            for (_, value) in approvals.iter() {
+                self.ensure_valid_approval(*value)?;
+            }
+
+            for (spender, value) in approvals {
+                self.approve(spender, value)?;
             }
             Ok(())
         }
 
-        /// Returns the contract owner
+        /// Transfers tokens from one account to another using allowance
         #[ink(message)]
-        pub fn owner(&self) -> H160 {
-            self.owner
-        }
-
-        /// Internal transfer function with checks
-        fn transfer_from_to(
+        pub fn transfer_from(
             &mut self,
-            from: &H160,
-            to: &H160,
+            from: H160,
+            to: H160,
             value: Balance,
         ) -> Result<()> {
-            // Check if contract is paused
-            if self.paused {
-                return Err(Error::Paused);
-            }
+            self.ensure_selector_open(ink::selector_bytes!("transfer_from"))?;
+            self.transfer_from_detailed(from, to, value)?;
+            Ok(())
+        }
+
+        /// Transfers tokens using allowance and returns `(allowance_deducted, remaining_allowance)`
+        #[ink(message)]
+        pub fn transfer_from_detailed(
+            &mut self,
+            from: H160,
+            to: H160,
+            value: Balance,
+        ) -> Result<(Balance, Balance)> {
+            self.ensure_selector_open(ink::selector_bytes!("transfer_from_detailed"))?;
+            let caller = self.env().caller();
+            let allowance = self.allowance(from, caller);
+
+            if allowance < value {
+                return Err(Error::InsufficientAllowance);
+            }
+
+            self.transfer_from_to(&from, &to, value, CATEGORY_USER)?;
+
+            // A Balance::MAX allowance is infinite and never decremented
+            if allowance == Balance::MAX {
+                return Ok((0, allowance));
+            }
+
+            let remaining = allowance.saturating_sub(value);
+            self.set_allowance(from, caller, remaining);
+
+            if allowance >= self.low_allowance_threshold && remaining < self.low_allowance_threshold {
+                self.env().emit_event(LowAllowance {
+                    owner: from,
+                    spender: caller,
+                    remaining,
+                });
+            }
+
+            Ok((value, remaining))
+        }
+
+        /// Mints new tokens to the caller's balance (only owner or minters)
+        #[ink(message)]
+        pub fn mint(&mut self, value: Balance) -> Result<()> {
+            self.ensure_selector_open(ink::selector_bytes!("mint"))?;
+            let caller = self.env().caller();
+            if caller != self.owner && !self.is_minter(caller) {
+                return Err(Error::Unauthorized);
+            }
+
+            self.mint_into(caller, value)
+        }
+
+        /// Mints new tokens directly to `to` (only owner or minters)
+        #[ink(message)]
+        pub fn mint_to(&mut self, to: H160, value: Balance) -> Result<()> {
+            self.ensure_selector_open(ink::selector_bytes!("mint_to"))?;
+            let caller = self.env().caller();
+            if caller != self.owner && !self.is_minter(caller) {
+                return Err(Error::Unauthorized);
+            }
+
+            self.mint_into(to, value)
+        }
+
+        /// Mints to each recipient independently, returning a result per entry instead of reverting (only owner or minters)
+        #[ink(message)]
+        pub fn batch_mint_lenient(&mut self, recipients: Vec<(H160, Balance)>) -> Vec<Result<()>> {
+            if self.is_selector_blocked(ink::selector_bytes!("batch_mint_lenient")) {
+                return recipients.iter().map(|_| Err(Error::Paused)).collect();
+            }
+
+            let caller = self.env().caller();
+            if caller != self.owner && !self.is_minter(caller) {
+                return recipients.iter().map(|_| Err(Error::Unauthorized)).collect();
+            }
+
+            recipients
+                .into_iter()
+                .map(|(to, value)| self.mint_into(to, value))
+                .collect()
+        }
+
+        /// Burns tokens from the caller's balance
+        #[ink(message)]
+        pub fn burn(&mut self, value: Balance) -> Result<()> {
+            self.ensure_selector_open(ink::selector_bytes!("burn"))?;
+            let caller = self.env().caller();
+            let balance = self.balance_of(caller);
+
+            if balance < value {
+                return Err(Error::InsufficientBalance);
+            }
+
+            let shares = self.shares_for(caller, value);
+            self.set_shares(caller, self.shares_of(caller).saturating_sub(shares));
+            self.total_shares = self.total_shares.saturating_sub(shares);
+            self.total_supply = self.to_tokens(self.total_shares);
+
+            // self.env().emit_event(Burn {
+            //     from: caller,
+            //     value,
+            // });
+
+            Self::emit_transfer(Some(caller), None, value, CATEGORY_BURN);
+
+            Ok(())
+        }
+
+        /// Burns tokens from `from` using the caller's allowance
+        #[ink(message)]
+        pub fn burn_from(&mut self, from: H160, value: Balance) -> Result<()> {
+            self.ensure_selector_open(ink::selector_bytes!("burn_from"))?;
+            let caller = self.env().caller();
+
+            if self.paused {
+                return Err(Error::Paused);
+            }
+
+            if self.is_blacklisted(from) || self.is_blacklisted(caller) {
+                return Err(Error::Blacklisted);
+            }
+
+            let allowance = self.allowance(from, caller);
+            if allowance < value {
+                return Err(Error::InsufficientAllowance);
+            }
+
+            let balance = self.balance_of(from);
+            if balance < value {
+                return Err(Error::InsufficientBalance);
+            }
+
+            let shares = self.shares_for(from, value);
+            self.set_shares(from, self.shares_of(from).saturating_sub(shares));
+            // A Balance::MAX allowance is infinite and never decremented, as in transfer_from
+            if allowance != Balance::MAX {
+                self.set_allowance(from, caller, allowance.saturating_sub(value));
+            }
+            self.total_shares = self.total_shares.saturating_sub(shares);
+            self.total_supply = self.to_tokens(self.total_shares);
+
+            self.env().emit_event(Burn { from, value });
+            Self::emit_transfer(Some(from), None, value, CATEGORY_BURN);
+
+            Ok(())
+        }
+
+        /// Increases allowance for a spender
+        #[ink(message)]
+        pub fn increase_allowance(&mut self, spender: H160, delta_value: Balance) -> Result<()> {
+            self.ensure_selector_open(ink::selector_bytes!("increase_allowance"))?;
+            let owner = self.env().caller();
+            let current_allowance = self.allowance(owner, spender);
+            let new_allowance = current_allowance.checked_add(delta_value).ok_or(Error::Overflow)?;
+            self.ensure_valid_approval(new_allowance)?;
+            self.set_allowance(owner, spender, new_allowance);
+
+            self.env().emit_event(Approval {
+                owner,
+                spender,
+                value: new_allowance,
+            });
+
+            Ok(())
+        }
+
+        /// Decreases allowance for a spender
+        #[ink(message)]
+        pub fn decrease_allowance(&mut self, spender: H160, delta_value: Balance) -> Result<()> {
+            self.ensure_selector_open(ink::selector_bytes!("decrease_allowance"))?;
+            let owner = self.env().caller();
+            let current_allowance = self.allowance(owner, spender);
+
+            if current_allowance < delta_value {
+                return Err(Error::InsufficientAllowance);
+            }
+
+            let new_allowance = current_allowance.saturating_sub(delta_value);
+            self.set_allowance(owner, spender, new_allowance);
+
+            self.env().emit_event(Approval {
+                owner,
+                spender,
+                value: new_allowance,
+            });
+
+            Ok(())
+        }
+
+        /// Pauses the contract (only owner or operator)
+        #[ink(message)]
+        pub fn pause(&mut self) -> Result<()> {
+            let caller = self.env().caller();
+            if !self.is_owner_or_operator(caller) {
+                return Err(Error::Unauthorized);
+            }
+
+            self.paused = true;
+
+            // self.env().emit_event(Paused { by: caller });
+
+            Ok(())
+        }
+
+        /// Unpauses the contract (only owner or operator)
+        #[ink(message)]
+        pub fn unpause(&mut self) -> Result<()> {
+            let caller = self.env().caller();
+            if !self.is_owner_or_operator(caller) {
+                return Err(Error::Unauthorized);
+            }
+
+            self.paused = false;
+
+            // self.env().emit_event(Unpaused { by: caller });
+
+            Ok(())
+        }
+
+        /// Returns whether the contract is paused
+        #[ink(message)]
+        pub fn is_paused(&self) -> bool {
+            self.paused
+        }
+
+        /// Blocks or unblocks a single message selector; messages that call a blocked message are blocked too.
+        /// Only user-facing messages check their selector, so owner and operator calls can't be blocked (only owner or operator)
+        #[ink(message)]
+        pub fn set_selector_blocked(&mut self, selector: [u8; 4], blocked: bool) -> Result<()> {
+            let caller = self.env().caller();
+            if !self.is_owner_or_operator(caller) {
+                return Err(Error::Unauthorized);
+            }
+
+            let was_blocked = self.is_selector_blocked(selector);
+            if blocked {
+                if !was_blocked {
+                    self.blocked_selector_count = self.blocked_selector_count.saturating_add(1);
+                }
+                self.blocked_selectors.insert(selector, &true);
+            } else {
+                if was_blocked {
+                    self.blocked_selector_count = self.blocked_selector_count.saturating_sub(1);
+                }
+                self.blocked_selectors.remove(selector);
+            }
+            Ok(())
+        }
+
+        /// Returns whether a message selector is blocked
+        #[ink(message)]
+        pub fn is_selector_blocked(&self, selector: [u8; 4]) -> bool {
+            self.blocked_selectors.get(selector).unwrap_or(false)
+        }
+
+        /// Adds an address to the blacklist (only owner or operator)
+        #[ink(message)]
+        pub fn blacklist_address(&mut self, account: H160) -> Result<()> {
+            let caller = self.env().caller();
+            if !self.is_owner_or_operator(caller) {
+                return Err(Error::Unauthorized);
+            }
+
+            self.blacklist.insert(account, &true);
+
+            // self.env().emit_event(Blacklisted { account });
+
+            Ok(())
+        }
+
+        /// Removes an address from the blacklist (only owner)
+        #[ink(message)]
+        pub fn remove_from_blacklist(&mut self, account: H160) -> Result<()> {
+            let caller = self.env().caller();
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
+            }
+
+            self.blacklist.remove(account);
+
+            // self.env().emit_event(RemovedFromBlacklist { account });
+
+            Ok(())
+        }
+
+        /// Checks if an address is blacklisted
+        #[ink(message)]
+        pub fn is_blacklisted(&self, account: H160) -> bool {
+            self.blacklist.get(account).unwrap_or(false)
+        }
+
+        /// Proposes a treasury account, which must call accept_treasury before finance admins can
+        /// spend from it; the token contract's own address takes effect immediately (only owner)
+        #[ink(message)]
+        pub fn set_treasury(&mut self, account: H160) -> Result<()> {
+            let caller = self.env().caller();
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
+            }
+
+            if account == self.env().address() {
+                self.treasury = account;
+                self.pending_treasury = None;
+            } else {
+                self.pending_treasury = Some(account);
+            }
+            Ok(())
+        }
+
+        /// Takes over the treasury role (only the pending treasury)
+        #[ink(message)]
+        pub fn accept_treasury(&mut self) -> Result<()> {
+            let caller = self.env().caller();
+            if self.pending_treasury != Some(caller) {
+                return Err(Error::Unauthorized);
+            }
+
+            self.treasury = caller;
+            self.pending_treasury = None;
+            Ok(())
+        }
+
+        /// Returns the treasury account
+        #[ink(message)]
+        pub fn treasury(&self) -> H160 {
+            self.treasury
+        }
+
+        /// Returns the proposed treasury account, if it has not accepted yet
+        #[ink(message)]
+        pub fn pending_treasury(&self) -> Option<H160> {
+            self.pending_treasury
+        }
+
+        /// Grants the finance admin role (only owner)
+        #[ink(message)]
+        pub fn add_finance_admin(&mut self, account: H160) -> Result<()> {
+            let caller = self.env().caller();
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
+            }
+
+            if !self.is_finance_admin(account) {
+                self.finance_admin_count = self.finance_admin_count.saturating_add(1);
+            }
+            self.finance_admins.insert(account, &true);
+            Ok(())
+        }
+
+        /// Revokes the finance admin role (only owner)
+        #[ink(message)]
+        pub fn remove_finance_admin(&mut self, account: H160) -> Result<()> {
+            let caller = self.env().caller();
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
+            }
+
+            if self.is_finance_admin(account) {
+                self.finance_admin_count = self.finance_admin_count.saturating_sub(1);
+            }
+            self.finance_admins.remove(account);
+            Ok(())
+        }
+
+        /// Checks if an address is a finance admin
+        #[ink(message)]
+        pub fn is_finance_admin(&self, account: H160) -> bool {
+            self.finance_admins.get(account).unwrap_or(false)
+        }
+
+        /// Grants the minter role (only owner)
+        #[ink(message)]
+        pub fn add_minter(&mut self, account: H160) -> Result<()> {
+            let caller = self.env().caller();
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
+            }
+
+            if !self.is_minter(account) {
+                self.minter_count = self.minter_count.saturating_add(1);
+            }
+            self.minters.insert(account, &true);
+            self.env().emit_event(MinterAdded { account });
+            Ok(())
+        }
+
+        /// Revokes the minter role (only owner)
+        #[ink(message)]
+        pub fn remove_minter(&mut self, account: H160) -> Result<()> {
+            let caller = self.env().caller();
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
+            }
+
+            if self.is_minter(account) {
+                self.minter_count = self.minter_count.saturating_sub(1);
+            }
+            self.minters.remove(account);
+            self.env().emit_event(MinterRemoved { account });
+            Ok(())
+        }
+
+        /// Checks if an address is a minter
+        #[ink(message)]
+        pub fn is_minter(&self, account: H160) -> bool {
+            self.minters.get(account).unwrap_or(false)
+        }
+
+        /// Moves tokens out of the treasury account (only finance admins)
+        #[ink(message)]
+        pub fn transfer_from_treasury(&mut self, to: H160, value: Balance) -> Result<()> {
+            self.ensure_selector_open(ink::selector_bytes!("transfer_from_treasury"))?;
+            let caller = self.env().caller();
+            if !self.is_finance_admin(caller) {
+                return Err(Error::Unauthorized);
+            }
+
+            let treasury = self.treasury;
+            self.transfer_from_to(&treasury, &to, value, CATEGORY_SYSTEM)
+        }
+
+        /// Freezes an account's outgoing transfers while still letting it receive (only owner)
+        #[ink(message)]
+        pub fn freeze(&mut self, account: H160) -> Result<()> {
+            let caller = self.env().caller();
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
+            }
+
+            if !self.is_frozen(account) {
+                self.frozen_count = self.frozen_count.saturating_add(1);
+            }
+            self.frozen.insert(account, &true);
+
+            self.env().emit_event(AccountFrozen { account });
+
+            Ok(())
+        }
+
+        /// Lifts an owner freeze (only owner)
+        #[ink(message)]
+        pub fn unfreeze(&mut self, account: H160) -> Result<()> {
+            let caller = self.env().caller();
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
+            }
+
+            if self.is_frozen(account) {
+                self.frozen_count = self.frozen_count.saturating_sub(1);
+            }
+            self.frozen.remove(account);
+
+            self.env().emit_event(AccountUnfrozen { account });
+
+            Ok(())
+        }
+
+        /// Checks if the owner froze an account's outgoing transfers
+        #[ink(message)]
+        pub fn is_frozen(&self, account: H160) -> bool {
+            self.frozen.get(account).unwrap_or(false)
+        }
+
+        /// Freezes the caller's outgoing transfers, e.g. when they suspect a compromise
+        #[ink(message)]
+        pub fn freeze_self(&mut self) -> Result<()> {
+            self.ensure_selector_open(ink::selector_bytes!("freeze_self"))?;
+            let caller = self.env().caller();
+            self.self_frozen.insert(caller, &true);
+            self.unfreeze_requests.remove(caller);
+            Ok(())
+        }
+
+        /// Starts the delay after which the caller can unfreeze their own account
+        #[ink(message)]
+        pub fn request_unfreeze(&mut self) -> Result<()> {
+            self.ensure_selector_open(ink::selector_bytes!("request_unfreeze"))?;
+            let caller = self.env().caller();
+            if !self.is_self_frozen(caller) {
+                return Err(Error::NotFrozen);
+            }
+
+            let available_at = self.env().block_timestamp().saturating_add(SELF_UNFREEZE_DELAY);
+            self.unfreeze_requests.insert(caller, &available_at);
+            Ok(())
+        }
+
+        /// Unfreezes the caller's account once the requested delay has passed
+        #[ink(message)]
+        pub fn unfreeze_self(&mut self) -> Result<()> {
+            self.ensure_selector_open(ink::selector_bytes!("unfreeze_self"))?;
+            let caller = self.env().caller();
+            if !self.is_self_frozen(caller) {
+                return Err(Error::NotFrozen);
+            }
+
+            match self.unfreeze_requests.get(caller) {
+                Some(available_at) if self.env().block_timestamp() >= available_at => {
+                    self.self_frozen.remove(caller);
+                    self.unfreeze_requests.remove(caller);
+                    Ok(())
+                }
+                _ => Err(Error::UnfreezeNotReady),
+            }
+        }
+
+        /// Checks if an account froze its own outgoing transfers
+        #[ink(message)]
+        pub fn is_self_frozen(&self, account: H160) -> bool {
+            self.self_frozen.get(account).unwrap_or(false)
+        }
+
+        /// Returns when a requested self-unfreeze becomes available
+        #[ink(message)]
+        pub fn unfreeze_available_at(&self, account: H160) -> Option<u64> {
+            self.unfreeze_requests.get(account)
+        }
+
+        /// Limits how much an account can receive per window (milliseconds) (only owner)
+        #[ink(message)]
+        pub fn set_received_cap(&mut self, account: H160, cap: Balance, window: u64) -> Result<()> {
+            let caller = self.env().caller();
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
+            }
+
+            self.received_caps.insert(account, &(cap, window));
+            self.received_in_window.remove(account);
+            Ok(())
+        }
+
+        /// Removes an account's received cap (only owner)
+        #[ink(message)]
+        pub fn remove_received_cap(&mut self, account: H160) -> Result<()> {
+            let caller = self.env().caller();
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
+            }
+
+            self.received_caps.remove(account);
+            self.received_in_window.remove(account);
+            Ok(())
+        }
+
+        /// Returns an account's received cap and window, if any
+        #[ink(message)]
+        pub fn received_cap_of(&self, account: H160) -> Option<(Balance, u64)> {
+            self.received_caps.get(account)
+        }
+
+        /// Returns how much an account has received in its current window
+        #[ink(message)]
+        pub fn received_in_window_of(&self, account: H160) -> Balance {
+            self.current_received_window(account).0
+        }
+
+        /// Adds a contract to the destination denylist (only owner)
+        #[ink(message)]
+        pub fn deny_contract(&mut self, account: H160) -> Result<()> {
+            let caller = self.env().caller();
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
+            }
+
+            self.contract_denylist.insert(account, &true);
+            Ok(())
+        }
+
+        /// Removes a contract from the destination denylist (only owner)
+        #[ink(message)]
+        pub fn allow_contract(&mut self, account: H160) -> Result<()> {
+            let caller = self.env().caller();
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
+            }
+
+            self.contract_denylist.remove(account);
+            Ok(())
+        }
+
+        /// Checks if an address is on the destination denylist
+        #[ink(message)]
+        pub fn is_contract_denied(&self, account: H160) -> bool {
+            self.contract_denylist.get(account).unwrap_or(false)
+        }
+
+        /// Sets an account's jurisdiction tag bitmask, 0 to untag it; the UNTAGGED_TAG bit is reserved (only owner)
+        #[ink(message)]
+        pub fn set_account_tags(&mut self, account: H160, tags: u32) -> Result<()> {
+            let caller = self.env().caller();
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
+            }
+
+            if tags & (1 << UNTAGGED_TAG) != 0 {
+                return Err(Error::InvalidTag);
+            }
+
+            if tags == 0 {
+                self.account_tags.remove(account);
+            } else {
+                self.account_tags.insert(account, &tags);
+            }
+            Ok(())
+        }
+
+        /// Returns an account's jurisdiction tag bitmask
+        #[ink(message)]
+        pub fn account_tags(&self, account: H160) -> u32 {
+            self.account_tags.get(account).unwrap_or(0)
+        }
+
+        /// Sets the recipient tags that holders of tag bit `from_tag` may transfer to. Use UNTAGGED_TAG
+        /// as `from_tag` or in `to_tags` for untagged accounts (only owner)
+        #[ink(message)]
+        pub fn set_allowed_tag_transitions(&mut self, from_tag: u8, to_tags: u32) -> Result<()> {
+            let caller = self.env().caller();
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
+            }
+
+            if u32::from(from_tag) >= u32::BITS {
+                return Err(Error::InvalidTag);
+            }
+
+            self.allowed_tag_transitions.insert(from_tag, &to_tags);
+            Ok(())
+        }
+
+        /// Returns the recipient tags that holders of tag bit `from_tag` may transfer to
+        #[ink(message)]
+        pub fn allowed_tag_transitions(&self, from_tag: u8) -> u32 {
+            self.allowed_tag_transitions.get(from_tag).unwrap_or(0)
+        }
+
+        /// Sets or clears the contract that must approve every transfer (only owner)
+        #[ink(message)]
+        pub fn set_transfer_validator(&mut self, validator: Option<H160>) -> Result<()> {
+            let caller = self.env().caller();
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
+            }
+
+            self.transfer_validator = validator;
+            Ok(())
+        }
+
+        /// Returns the contract that must approve every transfer, if any
+        #[ink(message)]
+        pub fn transfer_validator(&self) -> Option<H160> {
+            self.transfer_validator
+        }
+
+        /// Checks if code is deployed at an address
+        #[ink(message)]
+        pub fn is_contract_address(&self, account: H160) -> bool {
+            self.is_contract(account)
+        }
+
+        /// Batch transfer to multiple recipients; the whole batch is checked before any balance moves
+        #[ink(message)]
+        pub fn batch_transfer(&mut self, recipients: Vec<(H160, Balance)>) -> Result<()> {
+            self.ensure_selector_open(ink::selector_bytes!("batch_transfer"))?;
+            let caller = self.env().caller();
+            self.ensure_batch_transfer(caller, &recipients)?;
+
+            for (to, value) in recipients {
+                self.transfer(to, value)?;
+            }
+            Ok(())
+        }
+
+        /// Transfers the same `value` to every recipient; the airdrop only runs if the caller covers all of it
+        #[ink(message)]
+        pub fn batch_transfer_equal(&mut self, recipients: Vec<H160>, value: Balance) -> Result<()> {
+            self.ensure_selector_open(ink::selector_bytes!("batch_transfer_equal"))?;
+            let count = Balance::try_from(recipients.len()).map_err(|_| Error::Overflow)?;
+            value.checked_mul(count).ok_or(Error::Overflow)?;
+
+            let transfers = recipients
+                .into_iter()
+                .map(|to| (to, value))
+                .collect::<Vec<_>>();
+            self.batch_transfer(transfers)
+        }
+
+        /// Escrows `rate_per_sec * duration` from the caller and streams it to `to` over `duration` seconds
+        #[ink(message)]
+        pub fn create_stream(&mut self, to: H160, rate_per_sec: Balance, duration: u64) -> Result<u32> {
+            self.ensure_selector_open(ink::selector_bytes!("create_stream"))?;
+            let caller = self.env().caller();
+
+            if rate_per_sec == 0 || duration == 0 {
+                return Err(Error::ZeroAmount);
+            }
+
+            let total = rate_per_sec
+                .checked_mul(Balance::from(duration))
+                .ok_or(Error::Overflow)?;
+            duration.checked_mul(1000).ok_or(Error::Overflow)?;
+
+            self.ensure_stream_payout(caller, to, total)?;
+
+            if self.is_self_frozen(caller) || self.is_frozen(caller) {
+                return Err(Error::AccountFrozen);
+            }
+
+            if self.balance_of(caller) < total {
+                return Err(Error::InsufficientBalance);
+            }
+
+            let stream_id = self.next_stream_id;
+            self.next_stream_id = stream_id.checked_add(1).ok_or(Error::Overflow)?;
+
+            let shares = self.shares_for(caller, total);
+            let escrow = self.env().address();
+            self.move_shares(caller, escrow, shares);
+
+            self.streams.insert(stream_id, &Stream {
+                sender: caller,
+                recipient: to,
+                rate_per_sec,
+                start: self.env().block_timestamp(),
+                duration,
+                shares,
+                withdrawn_shares: 0,
+            });
+            Ok(stream_id)
+        }
+
+        /// Claims everything streamed so far and returns the amount (only the stream's recipient).
+        /// A payout the transfer policy rejects fails and stays claimable.
+        #[ink(message)]
+        pub fn withdraw_stream(&mut self, stream_id: u32) -> Result<Balance> {
+            self.ensure_selector_open(ink::selector_bytes!("withdraw_stream"))?;
+            let caller = self.env().caller();
+            let mut stream = self.streams.get(stream_id).ok_or(Error::StreamNotFound)?;
+            if caller != stream.recipient {
+                return Err(Error::Unauthorized);
+            }
+
+            let claimable = self.streamed_shares(&stream).saturating_sub(stream.withdrawn_shares);
+            self.ensure_stream_payout(stream.sender, stream.recipient, self.to_tokens(claimable))?;
+
+            let escrow = self.env().address();
+            let value = self.move_shares(escrow, stream.recipient, claimable);
+
+            stream.withdrawn_shares = stream.withdrawn_shares.saturating_add(claimable);
+            if stream.withdrawn_shares == stream.shares {
+                self.streams.remove(stream_id);
+            } else {
+                self.streams.insert(stream_id, &stream);
+            }
+            Ok(value)
+        }
+
+        /// Ends a stream, paying the recipient what has accrued and refunding the rest (only the stream's sender).
+        /// If the transfer policy blocks the recipient, the accrued amount stays in the stream for a later claim.
+        #[ink(message)]
+        pub fn cancel_stream(&mut self, stream_id: u32) -> Result<()> {
+            self.ensure_selector_open(ink::selector_bytes!("cancel_stream"))?;
+            let caller = self.env().caller();
+            let mut stream = self.streams.get(stream_id).ok_or(Error::StreamNotFound)?;
+            if caller != stream.sender {
+                return Err(Error::Unauthorized);
+            }
+
+            let streamed = self.streamed_shares(&stream);
+            let owed = streamed.saturating_sub(stream.withdrawn_shares);
+            let refund = stream.shares.saturating_sub(streamed);
+
+            let escrow = self.env().address();
+            if refund > 0 {
+                self.ensure_stream_payout(stream.sender, stream.sender, self.to_tokens(refund))?;
+                self.move_shares(escrow, stream.sender, refund);
+            }
+
+            let payout = self.ensure_stream_payout(stream.sender, stream.recipient, self.to_tokens(owed));
+            if owed > 0 && payout.is_err() {
+                // Stop accruing and keep what the recipient is owed until they can receive it
+                stream.shares = streamed;
+                stream.duration = 0;
+                self.streams.insert(stream_id, &stream);
+                return Ok(());
+            }
+
+            self.move_shares(escrow, stream.recipient, owed);
+            self.streams.remove(stream_id);
+            Ok(())
+        }
+
+        /// Returns a payment stream, if it is still open
+        #[ink(message)]
+        pub fn stream(&self, stream_id: u32) -> Option<Stream> {
+            self.streams.get(stream_id)
+        }
+
+        /// Returns how much the recipient of a stream could claim now
+        #[ink(message)]
+        pub fn stream_claimable(&self, stream_id: u32) -> Balance {
+            self.streams
+                .get(stream_id)
+                .map(|stream| self.to_tokens(self.streamed_shares(&stream).saturating_sub(stream.withdrawn_shares)))
+                .unwrap_or(0)
+        }
+
+        /// Returns the contract owner
+        #[ink(message)]
+        pub fn owner(&self) -> H160 {
+            self.owner
+        }
+
+        /// Proposes a new owner, who must call accept_ownership to take over (only owner)
+        #[ink(message)]
+        pub fn transfer_ownership(&mut self, new_owner: H160) -> Result<()> {
+            let caller = self.env().caller();
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
+            }
+
+            self.pending_owner = Some(new_owner);
+            Ok(())
+        }
+
+        /// Completes an ownership transfer (only the pending owner)
+        #[ink(message)]
+        pub fn accept_ownership(&mut self) -> Result<()> {
+            let caller = self.env().caller();
+            if self.pending_owner != Some(caller) {
+                return Err(Error::Unauthorized);
+            }
+
+            let previous = self.owner;
+            self.owner = caller;
+            self.pending_owner = None;
+
+            self.env().emit_event(OwnershipTransferred { previous, new: caller });
+            Ok(())
+        }
+
+        /// Permanently gives up ownership, leaving every owner-only call unusable. This is irreversible.
+        /// The operator and any pending owner are cleared as well. Fails while any minter or finance
+        /// admin remains, or while the token is paused, has blocked selectors or frozen accounts,
+        /// since nobody could lift them afterwards (only owner)
+        #[ink(message)]
+        pub fn renounce_ownership(&mut self) -> Result<()> {
+            let caller = self.env().caller();
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
+            }
+
+            if self.minter_count > 0 || self.finance_admin_count > 0 {
+                return Err(Error::RolesStillAssigned);
+            }
+
+            if self.paused || self.blocked_selector_count > 0 || self.frozen_count > 0 {
+                return Err(Error::RestrictionsActive);
+            }
+
+            self.owner = H160::zero();
+            self.pending_owner = None;
+            self.operator = None;
+
+            self.env().emit_event(OwnershipTransferred {
+                previous: caller,
+                new: H160::zero(),
+            });
+            Ok(())
+        }
+
+        /// Returns the proposed owner, if an ownership transfer is pending
+        #[ink(message)]
+        pub fn pending_owner(&self) -> Option<H160> {
+            self.pending_owner
+        }
+
+        /// Sets or clears the operator, who can pause, unpause and blacklist (only owner)
+        #[ink(message)]
+        pub fn set_operator(&mut self, operator: Option<H160>) -> Result<()> {
+            let caller = self.env().caller();
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
+            }
+
+            self.operator = operator;
+            Ok(())
+        }
+
+        /// Returns the operator, if set
+        #[ink(message)]
+        pub fn operator(&self) -> Option<H160> {
+            self.operator
+        }
+
+        /// Returns all admin parameters in one call
+        #[ink(message)]
+        pub fn config(&self) -> Config {
+            Config {
+                owner: self.owner,
+                pending_owner: self.pending_owner,
+                operator: self.operator,
+                paused: self.paused,
+                treasury: self.treasury,
+                decimals: self.decimals,
+                min_transfer_whole: self.min_transfer_whole,
+                max_supply: self.max_supply,
+                min_approval: self.min_approval,
+                max_approval: self.max_approval,
+                low_allowance_threshold: self.low_allowance_threshold,
+                native_fee: self.native_fee,
+                fee_collector: self.fee_collector,
+                max_holders: self.max_holders,
+                transfer_validator: self.transfer_validator,
+            }
+        }
+
+        /// Caps the number of holders, None to uncap; existing holders can always receive (only owner)
+        #[ink(message)]
+        pub fn set_max_holders(&mut self, max_holders: Option<u32>) -> Result<()> {
+            let caller = self.env().caller();
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
+            }
+
+            self.max_holders = max_holders;
+            Ok(())
+        }
+
+        /// Returns the maximum number of holders, if capped
+        #[ink(message)]
+        pub fn max_holders(&self) -> Option<u32> {
+            self.max_holders
+        }
+
+        /// Returns the number of accounts holding a nonzero balance
+        #[ink(message)]
+        pub fn holder_count(&self) -> u32 {
+            self.holder_count
+        }
+
+        /// Sets the native currency fee for transfer_with_native_fee (only owner)
+        #[ink(message)]
+        pub fn set_native_fee(&mut self, fee: U256) -> Result<()> {
+            let caller = self.env().caller();
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
+            }
+
+            self.native_fee = fee;
+            Ok(())
+        }
+
+        /// Queues a new fee collector, executable after FEE_COLLECTOR_DELAY (only owner)
+        #[ink(message)]
+        pub fn queue_fee_collector(&mut self, collector: H160) -> Result<()> {
+            let caller = self.env().caller();
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
+            }
+
+            let executable_at = self.env().block_timestamp().saturating_add(FEE_COLLECTOR_DELAY);
+            self.pending_fee_collector = Some((collector, executable_at));
+
+            self.env().emit_event(FeeCollectorQueued {
+                collector,
+                executable_at,
+            });
+            Ok(())
+        }
+
+        /// Applies the queued fee collector once its delay has passed (only owner)
+        #[ink(message)]
+        pub fn execute_fee_collector(&mut self) -> Result<()> {
+            let caller = self.env().caller();
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
+            }
+
+            let (collector, executable_at) = self.pending_fee_collector.ok_or(Error::NoFeeCollectorQueued)?;
+            if self.env().block_timestamp() < executable_at {
+                return Err(Error::FeeCollectorNotReady);
+            }
+
+            let old_collector = self.fee_collector;
+            self.fee_collector = collector;
+            self.pending_fee_collector = None;
+
+            self.env().emit_event(FeeCollectorChanged {
+                old_collector,
+                new_collector: collector,
+            });
+            Ok(())
+        }
+
+        /// Returns the account receiving native fees
+        #[ink(message)]
+        pub fn fee_collector(&self) -> H160 {
+            self.fee_collector
+        }
+
+        /// Returns the queued fee collector and when it becomes executable, if any
+        #[ink(message)]
+        pub fn pending_fee_collector(&self) -> Option<(H160, u64)> {
+            self.pending_fee_collector
+        }
+
+        /// Returns the native currency fee for transfer_with_native_fee
+        #[ink(message)]
+        pub fn native_fee(&self) -> U256 {
+            self.native_fee
+        }
+
+        /// Sets the smallest nonzero allowance that can be approved (only owner)
+        #[ink(message)]
+        pub fn set_min_approval(&mut self, value: Balance) -> Result<()> {
+            let caller = self.env().caller();
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
+            }
+
+            self.min_approval = value;
+            Ok(())
+        }
+
+        /// Returns the smallest nonzero allowance that can be approved
+        #[ink(message)]
+        pub fn min_approval(&self) -> Balance {
+            self.min_approval
+        }
+
+        /// Sets the largest allowance that can be approved, `None` for no cap (only owner)
+        #[ink(message)]
+        pub fn set_max_approval(&mut self, value: Option<Balance>) -> Result<()> {
+            let caller = self.env().caller();
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
+            }
+
+            self.max_approval = value;
+            Ok(())
+        }
+
+        /// Returns the largest allowance that can be approved, if capped
+        #[ink(message)]
+        pub fn max_approval(&self) -> Option<Balance> {
+            self.max_approval
+        }
+
+        /// Sets the allowance level below which transfer_from emits LowAllowance, 0 to disable (only owner)
+        #[ink(message)]
+        pub fn set_low_allowance_threshold(&mut self, threshold: Balance) -> Result<()> {
+            let caller = self.env().caller();
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
+            }
+
+            self.low_allowance_threshold = threshold;
+            Ok(())
+        }
+
+        /// Returns the low allowance threshold
+        #[ink(message)]
+        pub fn low_allowance_threshold(&self) -> Balance {
+            self.low_allowance_threshold
+        }
+
+        /// Sets the minimum transfer in whole tokens, 0 to disable (only owner)
+        #[ink(message)]
+        pub fn set_min_transfer_whole(&mut self, value: Balance) -> Result<()> {
+            let caller = self.env().caller();
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
+            }
+
+            self.min_transfer_whole = value;
+            Ok(())
+        }
+
+        /// Returns the minimum transfer in whole tokens
+        #[ink(message)]
+        pub fn min_transfer_whole(&self) -> Balance {
+            self.min_transfer_whole
+        }
+
+        /// Returns the minimum transfer in base units, saturating if the scaling overflows
+        #[ink(message)]
+        pub fn min_transfer(&self) -> Balance {
+            let unit = Balance::from(10u8)
+                .checked_pow(u32::from(self.decimals))
+                .unwrap_or(Balance::MAX);
+            self.min_transfer_whole.saturating_mul(unit)
+        }
+
+        /// Returns whether code is deployed at the given address
+        fn is_contract(&self, account: H160) -> bool {
+            match self.env().code_hash(&account) {
+                Ok(hash) => hash != H256::zero() && hash != H256::from(EMPTY_CODE_HASH),
+                Err(_) => false,
+            }
+        }
+
+        /// Emits Transfer together with its TransferCategorized companion
+        fn emit_transfer(from: Option<H160>, to: Option<H160>, value: Balance, category: u8) {
+            Self::env().emit_event(Transfer { from, to, value });
+            Self::env().emit_event(TransferCategorized {
+                from,
+                to,
+                value,
+                category,
+            });
+        }
+
+        /// Credits newly minted tokens to `to`, respecting the supply cap and recipient checks
+        fn mint_into(&mut self, to: H160, value: Balance) -> Result<()> {
+            if to == H160::zero() {
+                return Err(Error::ZeroAddress);
+            }
+
+            if value == 0 {
+                return Err(Error::ZeroAmount);
+            }
+
+            if self.is_blacklisted(to) {
+                return Err(Error::Blacklisted);
+            }
+
+            self.ensure_within_cap(value)?;
+            self.ensure_holder_capacity(to, value, false)?;
+            self.total_supply.checked_add(value).ok_or(Error::Overflow)?;
+
+            // Shares round down so a mint never creates more than `value`; amounts worth no share are refused
+            let shares = self.to_shares(value);
+            if shares == 0 {
+                return Err(Error::ZeroAmount);
+            }
+
+            let new_total_shares = self.total_shares.checked_add(shares).ok_or(Error::Overflow)?;
+            let new_shares = self.shares_of(to).checked_add(shares).ok_or(Error::Overflow)?;
+
+            let old_supply = self.total_supply;
+            self.set_shares(to, new_shares);
+            self.total_shares = new_total_shares;
+            self.total_supply = self.to_tokens(new_total_shares);
+
+            let minted = self.total_supply.saturating_sub(old_supply);
+            Self::emit_transfer(None, Some(to), minted, CATEGORY_MINT);
+
+            Ok(())
+        }
+
+        /// Returns (received, window start) for an account, starting a fresh window once the last one ended
+        fn current_received_window(&self, account: H160) -> (Balance, u64) {
+            let now = self.env().block_timestamp();
+            let window = self.received_caps.get(account).map(|(_, window)| window).unwrap_or(0);
+
+            match self.received_in_window.get(account) {
+                Some((received, window_start)) if now < window_start.saturating_add(window) => {
+                    (received, window_start)
+                }
+                _ => (0, now),
+            }
+        }
+
+        /// Fails if minting `value` would push the total supply above the cap
+        fn ensure_within_cap(&self, value: Balance) -> Result<()> {
+            if self.max_supply != 0 && self.total_supply.saturating_add(value) > self.max_supply {
+                return Err(Error::CapExceeded);
+            }
+            Ok(())
+        }
+
+        /// Pre-flight for batch transfers: fails unless `from` can cover the whole batch and every
+        /// recipient can receive, so a batch never stops half way for these reasons
+        fn ensure_batch_transfer(&self, from: H160, recipients: &[(H160, Balance)]) -> Result<()> {
+            if self.paused {
+                return Err(Error::Paused);
+            }
+
+            if self.is_blacklisted(from) {
+                return Err(Error::Blacklisted);
+            }
+
+            if self.is_self_frozen(from) || self.is_frozen(from) {
+                return Err(Error::AccountFrozen);
+            }
+
+            let min_transfer = self.min_transfer();
+            let mut total: Balance = 0;
+            for (to, value) in recipients {
+                if *to == H160::zero() {
+                    return Err(Error::ZeroAddress);
+                }
+
+                if self.is_blacklisted(*to) {
+                    return Err(Error::Blacklisted);
+                }
+
+                self.ensure_tags_compatible(from, *to)?;
+
+                if *value < min_transfer {
+                    return Err(Error::BelowMinimum);
+                }
+
+                total = total.checked_add(*value).ok_or(Error::Overflow)?;
+            }
+
+            if self.balance_of(from) < total {
+                return Err(Error::InsufficientBalance);
+            }
+
+            Ok(())
+        }
+
+        /// Fails unless every tag of the sender allows at least one tag of the recipient.
+        /// Untagged accounts count as UNTAGGED_TAG, so they can't relay tokens between tags the table keeps apart;
+        /// only transfers between two untagged accounts are unrestricted.
+        fn ensure_tags_compatible(&self, from: H160, to: H160) -> Result<()> {
+            let from_tags = self.account_tags(from);
+            let to_tags = self.account_tags(to);
+            if from_tags == 0 && to_tags == 0 {
+                return Ok(());
+            }
+
+            let untagged = 1 << UNTAGGED_TAG;
+            let from_tags = if from_tags == 0 { untagged } else { from_tags };
+            let to_tags = if to_tags == 0 { untagged } else { to_tags };
+
+            for tag in 0..u32::BITS as u8 {
+                if from_tags & (1 << tag) != 0 && self.allowed_tag_transitions(tag) & to_tags == 0 {
+                    return Err(Error::TransferRestricted);
+                }
+            }
+            Ok(())
+        }
+
+        /// Asks the transfer validator, if set, to approve the transfer; a failed call counts as a rejection
+        fn ensure_transfer_validated(&self, from: H160, to: H160, value: Balance) -> Result<()> {
+            let Some(validator) = self.transfer_validator else {
+                return Ok(());
+            };
+
+            let approved = build_call::<DefaultEnvironment>()
+                .call(validator)
+                .transferred_value(U256::zero())
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink::selector_bytes!("validate_transfer")))
+                        .push_arg(from)
+                        .push_arg(to)
+                        .push_arg(value)
+                )
+                .returns::<bool>()
+                .try_invoke()
+                .unwrap_or(Ok(false))
+                .unwrap_or(false);
+
+            if !approved {
+                return Err(Error::TransferRestricted);
+            }
+            Ok(())
+        }
+
+        /// Escrowed shares a stream has released by now, claimed or not
+        fn streamed_shares(&self, stream: &Stream) -> Balance {
+            if stream.duration == 0 {
+                return stream.shares;
+            }
+
+            let duration_ms = stream.duration.saturating_mul(1000);
+            let elapsed = self.env().block_timestamp().saturating_sub(stream.start).min(duration_ms);
+            let streamed = U256::from(stream.shares) * U256::from(elapsed) / U256::from(duration_ms);
+            Balance::try_from(streamed).unwrap_or(Balance::MAX)
+        }
+
+        /// Runs the recipient side of the transfer policy for a stream payout of `value` from `sender`'s escrow.
+        /// The sender's freeze and the minimum transfer are left out so accrued amounts can always be claimed.
+        fn ensure_stream_payout(&self, sender: H160, recipient: H160, value: Balance) -> Result<()> {
+            if self.paused {
+                return Err(Error::Paused);
+            }
+
+            if recipient == H160::zero() {
+                return Err(Error::ZeroAddress);
+            }
+
+            if self.is_blacklisted(sender) || self.is_blacklisted(recipient) {
+                return Err(Error::Blacklisted);
+            }
+
+            if self.is_contract_denied(recipient) && self.is_contract(recipient) {
+                return Err(Error::DeniedContract);
+            }
+
+            self.ensure_tags_compatible(sender, recipient)?;
+            self.ensure_holder_capacity(recipient, value, false)?;
+            self.ensure_transfer_validated(sender, recipient, value)
+        }
+
+        /// Moves shares between accounts, returning their token value. Only used for stream escrow,
+        /// after ensure_stream_payout has run the transfer policy.
+        fn move_shares(&mut self, from: H160, to: H160, shares: Balance) -> Balance {
+            if shares == 0 {
+                return 0;
+            }
+
+            let value = self.to_tokens(shares);
+            self.set_shares(from, self.shares_of(from).saturating_sub(shares));
+            self.set_shares(to, self.shares_of(to).saturating_add(shares));
+            Self::emit_transfer(Some(from), Some(to), value, CATEGORY_SYSTEM);
+            value
+        }
+
+        /// Fails with `Paused` if the message selector has been blocked
+        fn ensure_selector_open(&self, selector: [u8; 4]) -> Result<()> {
+            if self.is_selector_blocked(selector) {
+                return Err(Error::Paused);
+            }
+            Ok(())
+        }
+
+        /// Returns whether the account is the owner or the operator
+        fn is_owner_or_operator(&self, account: H160) -> bool {
+            account == self.owner || self.operator == Some(account)
+        }
+
+        /// Converts a token amount to shares at the current rate, rounding down
+        fn to_shares(&self, value: Balance) -> Balance {
+            let shares = U256::from(value) * self.shares_per_token / U256::from(SHARE_UNIT);
+            Balance::try_from(shares).unwrap_or(Balance::MAX)
+        }
+
+        /// Converts a token amount to shares at the current rate, rounding up
+        fn to_shares_up(&self, value: Balance) -> Balance {
+            let unit = U256::from(SHARE_UNIT);
+            let shares = (U256::from(value) * self.shares_per_token + unit - U256::from(1)) / unit;
+            Balance::try_from(shares).unwrap_or(Balance::MAX)
+        }
+
+        /// Converts shares to a token amount at the current rate
+        fn to_tokens(&self, shares: Balance) -> Balance {
+            let value = U256::from(shares) * U256::from(SHARE_UNIT) / self.shares_per_token;
+            Balance::try_from(value).unwrap_or(Balance::MAX)
+        }
+
+        /// Shares to take from `account` to move `value` tokens, rounded up so a nonzero amount never
+        /// moves zero shares; moving the whole balance takes every share
+        fn shares_for(&self, account: H160, value: Balance) -> Balance {
+            let shares = self.shares_of(account);
+            if value >= self.balance_of(account) {
+                shares
+            } else {
+                self.to_shares_up(value).min(shares)
+            }
+        }
+
+        /// Writes an account's shares, keeping the holder count in sync
+        fn set_shares(&mut self, account: H160, new_shares: Balance) {
+            let current_shares = self.shares_of(account);
+            if current_shares == 0 && new_shares > 0 {
+                self.holder_count = self.holder_count.saturating_add(1);
+            } else if current_shares > 0 && new_shares == 0 {
+                self.holder_count = self.holder_count.saturating_sub(1);
+            }
+
+            if new_shares == 0 {
+                self.balances.remove(account);
+            } else {
+                self.balances.insert(account, &new_shares);
+            }
+        }
+
+        /// Fails if crediting `value` to `to` would add a holder beyond max_holders.
+        /// `sender_leaves` is true when the same move empties another holder's balance.
+        fn ensure_holder_capacity(&self, to: H160, value: Balance, sender_leaves: bool) -> Result<()> {
+            if let Some(max_holders) = self.max_holders {
+                let new_holder = value > 0 && self.shares_of(to) == 0;
+                if new_holder && !sender_leaves && self.holder_count >= max_holders {
+                    return Err(Error::HolderLimitReached);
+                }
+            }
+            Ok(())
+        }
+
+        /// Writes an allowance, keeping the owner's spender list in sync
+        fn set_allowance(&mut self, owner: H160, spender: H160, value: Balance) {
+            self.allowances.insert((owner, spender), &value);
+
+            let mut spenders = self.spenders_of(owner);
+            match spenders.iter().position(|s| *s == spender) {
+                None if value > 0 => spenders.push(spender),
+                Some(index) if value == 0 => {
+                    spenders.swap_remove(index);
+                }
+                _ => return,
+            }
+            self.spenders.insert(owner, &spenders);
+        }
+
+        /// Checks an allowance against the approval limits; zero is always allowed to revoke
+        fn ensure_valid_approval(&self, value: Balance) -> Result<()> {
+            if value != 0 && value < self.min_approval {
+                return Err(Error::BelowMinimum);
+            }
+            if let Some(max_approval) = self.max_approval {
+                if value > max_approval {
+                    return Err(Error::ApprovalTooLarge);
+                }
+            }
+            Ok(())
+        }
+
+        /// Internal transfer function with checks
+        fn transfer_from_to(
+            &mut self,
+            from: &H160,
+            to: &H160,
+            value: Balance,
+            category: u8,
+        ) -> Result<()> {
+            // Check if contract is paused
+            if self.paused {
+                return Err(Error::Paused);
+            }
+
+            // Tokens sent to the zero address would be lost; burning goes through burn
+            if *to == H160::zero() {
+                return Err(Error::ZeroAddress);
+            }
+
+            // Check if sender or recipient is blacklisted
+            if self.is_blacklisted(*from) || self.is_blacklisted(*to) {
+                return Err(Error::Blacklisted);
+            }
+
+            // Check if sender froze their own account or was frozen by the owner
+            if self.is_self_frozen(*from) || self.is_frozen(*from) {
+                return Err(Error::AccountFrozen);
+            }
+
+            // Check if recipient is a denylisted contract
+            if self.is_contract_denied(*to) && self.is_contract(*to) {
+                return Err(Error::DeniedContract);
+            }
+
+            self.ensure_tags_compatible(*from, *to)?;
+
+            if value < self.min_transfer() {
+                return Err(Error::BelowMinimum);
+            }
+
+            let from_balance = self.balance_of(*from);
+            if from_balance < value {
+                return Err(Error::InsufficientBalance);
+            }
+
+            // Check the recipient's received cap for the current window
+            let received_window = match self.received_caps.get(to) {
+                Some((cap, _)) => {
+                    let (received, window_start) = self.current_received_window(*to);
+                    let received = received.saturating_add(value);
+                    if received > cap {
+                        return Err(Error::ReceiveCapExceeded);
+                    }
+                    Some((received, window_start))
+                }
+                None => None,
+            };
+
+            let from_shares = self.shares_of(*from);
+            let shares = self.shares_for(*from, value);
+
+            // A self-transfer leaves the shares unchanged
+            let new_to_shares = if from == to {
+                from_shares
+            } else {
+                self.shares_of(*to).checked_add(shares).ok_or(Error::Overflow)?
+            };
+
+            self.ensure_holder_capacity(*to, value, shares == from_shares)?;
+
+            // The external validator runs last so rejected transfers never pay for the call
+            self.ensure_transfer_validated(*from, *to, value)?;
+
+            self.set_shares(*from, from_shares.saturating_sub(shares));
+            self.set_shares(*to, new_to_shares);
+
+            if let Some(received_window) = received_window {
+                self.received_in_window.insert(to, &received_window);
+            }
+
+            Self::emit_transfer(Some(*from), Some(*to), value, category);
+
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use ink::env::test;
+
+        fn get_default_accounts() -> test::DefaultAccounts {
+            test::default_accounts()
+        }
+
+        fn get_bob() -> H160 {
+            H160::from([2u8; 20])
+        }
+
+        fn get_charlie() -> H160 {
+            H160::from([3u8; 20])
+        }
+
+        fn decode_transfer(event: &test::EmittedEvent) -> Transfer {
+            <Transfer as scale::Decode>::decode(&mut &event.data[..]).expect("invalid Transfer event data")
+        }
+
+        fn decode_category(event: &test::EmittedEvent) -> TransferCategorized {
+            <TransferCategorized as scale::Decode>::decode(&mut &event.data[..])
+                .expect("invalid TransferCategorized event data")
+        }
+
+        fn decode_approval(event: &test::EmittedEvent) -> Approval {
+            <Approval as scale::Decode>::decode(&mut &event.data[..]).expect("invalid Approval event data")
+        }
+
+        fn set_timestamp(timestamp: u64) {
+            test::set_block_timestamp::<ink::env::DefaultEnvironment>(timestamp);
+        }
+
+        #[ink::test]
+        fn new_works() {
+            let token = Token::new(1000);
+            assert_eq!(token.total_supply(), 1000);
+        }
+
+        #[ink::test]
+        fn constructor_emits_transfer() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let _token = Token::new(1000);
+
+            let events = test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(events.len(), 2);
+            let event = decode_transfer(&events[0]);
+            assert_eq!(event.from, None);
+            assert_eq!(event.to, Some(accounts.alice));
+            assert_eq!(event.value, 1000);
+            assert_eq!(decode_category(&events[1]).category, CATEGORY_MINT);
+        }
+
+        #[ink::test]
+        fn transfer_events_work() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+
+            // Every operation emits Transfer followed by TransferCategorized
+            // Transfer
+            assert!(token.transfer(bob, 100).is_ok());
+            let events = test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(events.len(), 4);
+            let event = decode_transfer(&events[2]);
+            assert_eq!(event.from, Some(accounts.alice));
+            assert_eq!(event.to, Some(bob));
+            assert_eq!(event.value, 100);
+
+            // Mint
+            assert!(token.mint(50).is_ok());
+            let events = test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(events.len(), 6);
+            let event = decode_transfer(&events[4]);
+            assert_eq!(event.from, None);
+            assert_eq!(event.to, Some(accounts.alice));
+            assert_eq!(event.value, 50);
+
+            // Burn
+            assert!(token.burn(30).is_ok());
+            let events = test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(events.len(), 8);
+            let event = decode_transfer(&events[6]);
+            assert_eq!(event.from, Some(accounts.alice));
+            assert_eq!(event.to, None);
+            assert_eq!(event.value, 30);
+
+            // Every Transfer carries the same topic layout
+            assert!(events.iter().step_by(2).all(|event| event.topics.len() == events[0].topics.len()));
+
+            // Failed operations emit nothing
+            assert!(token.transfer(bob, 10_000).is_err());
+            assert!(token.burn(10_000).is_err());
+            assert_eq!(test::recorded_events().count(), 8);
+        }
+
+        #[ink::test]
+        fn transfer_categories_work() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+
+            // User transfer
+            assert!(token.transfer(bob, 100).is_ok());
+            let events = test::recorded_events().collect::<Vec<_>>();
+            let event = decode_category(&events[events.len() - 1]);
+            assert_eq!(event.from, Some(accounts.alice));
+            assert_eq!(event.to, Some(bob));
+            assert_eq!(event.value, 100);
+            assert_eq!(event.category, CATEGORY_USER);
+
+            // Paying a native fee does not change the token transfer's category
+            test::set_value_transferred(U256::zero());
+            assert!(token.transfer_with_native_fee(bob, 10).is_ok());
+            let events = test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(decode_category(&events[events.len() - 1]).category, CATEGORY_USER);
+
+            // Mint
+            assert!(token.mint(50).is_ok());
+            let events = test::recorded_events().collect::<Vec<_>>();
+            let event = decode_category(&events[events.len() - 1]);
+            assert_eq!(event.from, None);
+            assert_eq!(event.to, Some(accounts.alice));
+            assert_eq!(event.category, CATEGORY_MINT);
+
+            // Treasury payout
+            assert!(token.add_finance_admin(accounts.alice).is_ok());
+            assert!(token.transfer_from_treasury(bob, 5).is_ok());
+            let events = test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(decode_category(&events[events.len() - 1]).category, CATEGORY_SYSTEM);
+        }
+
+        #[ink::test]
+        fn new_with_metadata_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token = Token::new_with_metadata(1000, b"Piggy Token".to_vec(), b"PIG".to_vec(), 12);
+            assert_eq!(token.token_name(), b"Piggy Token".to_vec());
+            assert_eq!(token.token_symbol(), b"PIG".to_vec());
+            assert_eq!(token.token_decimals(), 12);
+            assert_eq!(token.total_supply(), 1000);
+        }
+
+        #[ink::test]
+        fn default_metadata_is_empty() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token = Token::new(1000);
+            assert!(token.token_name().is_empty());
+            assert!(token.token_symbol().is_empty());
+            assert_eq!(token.token_decimals(), 18);
+        }
+
+        #[ink::test]
+        fn balance_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token = Token::new(1000);
+            let bob = get_bob();
+
+            assert_eq!(token.balance_of(accounts.alice), 1000);
+            assert_eq!(token.balance_of(bob), 0);
+        }
+
+        #[ink::test]
+        fn transfer_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+
+            assert_eq!(token.balance_of(accounts.alice), 1000);
+            assert_eq!(token.balance_of(bob), 0);
+
+            assert!(token.transfer(bob, 100).is_ok());
+
+            assert_eq!(token.balance_of(accounts.alice), 900);
+            assert_eq!(token.balance_of(bob), 100);
+        }
+
+        #[ink::test]
+        fn transfer_to_zero_address_fails() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let zero = H160::from([0u8; 20]);
+
+            assert_eq!(token.transfer(zero, 100), Err(Error::ZeroAddress));
+
+            assert!(token.approve(get_bob(), 100).is_ok());
+            test::set_caller(get_bob());
+            assert_eq!(token.transfer_from(accounts.alice, zero, 100), Err(Error::ZeroAddress));
+
+            assert_eq!(token.balance_of(zero), 0);
+            assert_eq!(token.balance_of(accounts.alice), 1000);
+            assert_eq!(token.allowance(accounts.alice, get_bob()), 100);
+
+            // Other recipients are unaffected
+            assert!(token.transfer_from(accounts.alice, get_charlie(), 100).is_ok());
+            assert_eq!(token.balance_of(get_charlie()), 100);
+        }
+
+        #[ink::test]
+        fn transfer_insufficient_balance_fails() {
+            let mut token = Token::new(100);
+            let bob = get_bob();
+
+            let result = token.transfer(bob, 200);
+            assert_eq!(result, Err(Error::InsufficientBalance));
+        }
+
+        #[ink::test]
+        fn approve_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+
+            assert_eq!(token.allowance(accounts.alice, bob), 0);
+            assert!(token.approve(bob, 100).is_ok());
+            assert_eq!(token.allowance(accounts.alice, bob), 100);
+        }
+
+        #[ink::test]
+        fn batch_approve_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+            let charlie = get_charlie();
+            let django = H160::from([4u8; 20]);
+            let before = test::recorded_events().count();
+
+            assert!(token.batch_approve(vec![(bob, 100), (charlie, 200), (django, 300), (bob, 150)]).is_ok());
+            assert_eq!(token.allowance(accounts.alice, bob), 150);
+            assert_eq!(token.allowance(accounts.alice, charlie), 200);
+            assert_eq!(token.allowance(accounts.alice, django), 300);
+
+            // One Approval per entry
+            let events = test::recorded_events().skip(before).collect::<Vec<_>>();
+            assert_eq!(events.len(), 4);
+            assert_eq!(decode_approval(&events[3]).value, 150);
+
+            // An invalid entry rejects the whole batch
+            assert!(token.set_max_approval(Some(500)).is_ok());
+            let result = token.batch_approve(vec![(bob, 10), (charlie, 501)]);
+            assert_eq!(result, Err(Error::ApprovalTooLarge));
+            assert_eq!(token.allowance(accounts.alice, bob), 150);
+        }
+
+        #[ink::test]
+        fn batch_approve_is_capped() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+
+            assert!(token.batch_approve(vec![(bob, 10); MAX_BATCH_APPROVALS]).is_ok());
+            assert_eq!(token.allowance(accounts.alice, bob), 10);
+
+            let result = token.batch_approve(vec![(bob, 20); MAX_BATCH_APPROVALS + 1]);
+            assert_eq!(result, Err(Error::BatchTooLarge));
+            assert_eq!(token.allowance(accounts.alice, bob), 10);
+        }
+
+        #[ink::test]
+        fn approve_returns_previous_allowance() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+
+            assert_eq!(token.approve(bob, 100), Ok(0));
+            assert_eq!(token.approve(bob, 250), Ok(100));
+            assert_eq!(token.approve(bob, 0), Ok(250));
+            assert_eq!(token.approve(bob, 40), Ok(0));
+            assert_eq!(token.approve(get_charlie(), 10), Ok(0));
+
+            // A rejected approval leaves the allowance in place
+            assert!(token.set_max_approval(Some(50)).is_ok());
+            assert_eq!(token.approve(bob, 51), Err(Error::ApprovalTooLarge));
+            assert_eq!(token.approve(bob, 50), Ok(40));
+        }
+
+        #[ink::test]
+        fn transfer_from_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+            let charlie = get_charlie();
+
+            // Approve Bob to spend tokens
+            assert!(token.approve(bob, 100).is_ok());
+
+            // Set caller to Bob for transfer_from
+            test::set_caller(bob);
+
+            // Bob transfers from alice to Charlie
+            assert!(token.transfer_from(accounts.alice, charlie, 50).is_ok());
+
+            // Check balances
+            assert_eq!(token.balance_of(accounts.alice), 950);
+            assert_eq!(token.balance_of(charlie), 50);
+            assert_eq!(token.allowance(accounts.alice, bob), 50);
+        }
+
+        #[ink::test]
+        fn burn_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+
+            assert!(token.burn(100).is_ok());
+            assert_eq!(token.balance_of(accounts.alice), 900);
+            assert_eq!(token.total_supply(), 900);
+        }
+
+        #[ink::test]
+        fn burn_from_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+            assert!(token.approve(bob, 300).is_ok());
+
+            test::set_caller(bob);
+            let before = test::recorded_events().count();
+            assert!(token.burn_from(accounts.alice, 200).is_ok());
+            assert_eq!(token.balance_of(accounts.alice), 800);
+            assert_eq!(token.allowance(accounts.alice, bob), 100);
+            assert_eq!(token.total_supply(), 800);
+
+            let events = test::recorded_events().skip(before).collect::<Vec<_>>();
+            assert_eq!(events.len(), 3);
+            let event = <Burn as scale::Decode>::decode(&mut &events[0].data[..])
+                .expect("invalid Burn event data");
+            assert_eq!(event.from, accounts.alice);
+            assert_eq!(event.value, 200);
+            let event = decode_transfer(&events[1]);
+            assert_eq!(event.from, Some(accounts.alice));
+            assert_eq!(event.to, None);
+            assert_eq!(event.value, 200);
+
+            // An infinite allowance stays infinite
+            test::set_caller(accounts.alice);
+            assert!(token.approve(bob, Balance::MAX).is_ok());
+            test::set_caller(bob);
+            assert!(token.burn_from(accounts.alice, 100).is_ok());
+            assert_eq!(token.allowance(accounts.alice, bob), Balance::MAX);
+            assert_eq!(token.total_supply(), 700);
+        }
+
+        #[ink::test]
+        fn burn_from_rejects_bad_calls() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+            assert!(token.approve(bob, 2000).is_ok());
+
+            test::set_caller(bob);
+            assert_eq!(token.burn_from(get_charlie(), 1), Err(Error::InsufficientAllowance));
+            assert_eq!(token.burn_from(accounts.alice, 1001), Err(Error::InsufficientBalance));
+
+            test::set_caller(accounts.alice);
+            assert!(token.pause().is_ok());
+            test::set_caller(bob);
+            assert_eq!(token.burn_from(accounts.alice, 100), Err(Error::Paused));
+
+            test::set_caller(accounts.alice);
+            assert!(token.unpause().is_ok());
+            assert!(token.blacklist_address(bob).is_ok());
+            test::set_caller(bob);
+            assert_eq!(token.burn_from(accounts.alice, 100), Err(Error::Blacklisted));
+
+            assert_eq!(token.total_supply(), 1000);
+            assert_eq!(token.allowance(accounts.alice, bob), 2000);
+        }
+
+        #[ink::test]
+        fn pause_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+
+            assert!(!token.is_paused());
+            assert!(token.pause().is_ok());
+            assert!(token.is_paused());
+
+            let result = token.transfer(bob, 100);
+            assert_eq!(result, Err(Error::Paused));
+        }
+
+        #[ink::test]
+        fn blocked_selector_stops_only_that_message() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+            let transfer = ink::selector_bytes!("transfer");
+
+            assert!(!token.is_selector_blocked(transfer));
+            assert!(token.set_selector_blocked(transfer, true).is_ok());
+            assert!(token.is_selector_blocked(transfer));
+            assert!(!token.is_paused());
+
+            assert_eq!(token.transfer(bob, 100), Err(Error::Paused));
+            // Wrappers around transfer are blocked with it
+            assert_eq!(token.transfer_before(bob, 100, u64::MAX), Err(Error::Paused));
+
+            // Burning is untouched
+            assert!(token.burn(100).is_ok());
+            assert_eq!(token.total_supply(), 900);
+
+            assert!(token.set_selector_blocked(transfer, false).is_ok());
+            assert!(token.transfer(bob, 100).is_ok());
+            assert_eq!(token.balance_of(bob), 100);
+
+            test::set_caller(bob);
+            assert_eq!(token.set_selector_blocked(transfer, true), Err(Error::Unauthorized));
+        }
+
+        #[ink::test]
+        fn blocked_selectors_cover_every_entry_point() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+            test::set_callee(H160::from([9u8; 20]));
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+
+            let approve = ink::selector_bytes!("approve");
+            assert!(token.set_selector_blocked(approve, true).is_ok());
+            assert_eq!(token.approve(bob, 100), Err(Error::Paused));
+            assert_eq!(token.batch_approve(vec![(bob, 100)]), Err(Error::Paused));
+            assert!(token.increase_allowance(bob, 100).is_ok());
+
+            let batch_mint = ink::selector_bytes!("batch_mint_lenient");
+            assert!(token.set_selector_blocked(batch_mint, true).is_ok());
+            assert_eq!(token.batch_mint_lenient(vec![(bob, 100)]), vec![Err(Error::Paused)]);
+
+            let create_stream = ink::selector_bytes!("create_stream");
+            assert!(token.set_selector_blocked(create_stream, true).is_ok());
+            assert_eq!(token.create_stream(bob, 1, 100), Err(Error::Paused));
+
+            assert_eq!(token.total_supply(), 1000);
+            assert_eq!(token.balance_of(accounts.alice), 1000);
+        }
+
+        #[ink::test]
+        fn tag_transitions_restrict_transfers() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+            let charlie = get_charlie();
+            let us: u32 = 1 << 0;
+            let eu: u32 = 1 << 1;
+
+            assert!(token.set_account_tags(accounts.alice, us).is_ok());
+            assert!(token.set_account_tags(bob, us).is_ok());
+            assert!(token.set_account_tags(charlie, eu).is_ok());
+            assert_eq!(token.account_tags(charlie), eu);
+
+            // US holders may only send to US holders
+            assert!(token.set_allowed_tag_transitions(0, us).is_ok());
+            assert_eq!(token.allowed_tag_transitions(0), us);
+
+            assert!(token.transfer(bob, 100).is_ok());
+            assert_eq!(token.transfer(charlie, 100), Err(Error::TransferRestricted));
+            assert_eq!(token.batch_transfer(vec![(bob, 10), (charlie, 10)]), Err(Error::TransferRestricted));
+            assert_eq!(token.balance_of(bob), 100);
+
+            // An untagged account can't relay US tokens on to EU: both hops need a route
+            let untagged = H160::from([4u8; 20]);
+            let untagged_bit: u32 = 1 << UNTAGGED_TAG;
+            assert_eq!(token.transfer(untagged, 100), Err(Error::TransferRestricted));
+            assert!(token.set_allowed_tag_transitions(0, us | untagged_bit).is_ok());
+            assert!(token.transfer(untagged, 100).is_ok());
+
+            test::set_caller(untagged);
+            assert_eq!(token.transfer(charlie, 50), Err(Error::TransferRestricted));
+            assert!(token.transfer(H160::from([5u8; 20]), 10).is_ok());
+            assert_eq!(token.balance_of(charlie), 0);
+
+            test::set_caller(accounts.alice);
+            assert!(token.set_allowed_tag_transitions(UNTAGGED_TAG, eu).is_ok());
+            test::set_caller(untagged);
+            assert!(token.transfer(charlie, 50).is_ok());
+            assert_eq!(token.balance_of(charlie), 50);
+            test::set_caller(accounts.alice);
+
+            // Opening the US -> EU route allows the transfer
+            assert!(token.set_allowed_tag_transitions(0, us | eu).is_ok());
+            assert!(token.transfer(charlie, 100).is_ok());
+            assert_eq!(token.balance_of(charlie), 150);
+
+            assert_eq!(token.set_allowed_tag_transitions(32, us), Err(Error::InvalidTag));
+            assert_eq!(token.set_account_tags(bob, 1 << UNTAGGED_TAG), Err(Error::InvalidTag));
+
+            test::set_caller(bob);
+            assert_eq!(token.set_account_tags(bob, 0), Err(Error::Unauthorized));
+            assert_eq!(token.set_allowed_tag_transitions(1, eu), Err(Error::Unauthorized));
+        }
+
+        #[ink::test]
+        fn transfer_validator_is_owner_managed() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+            let validator = H160::from([9u8; 20]);
+
+            // Without a validator transfers need no external approval
+            assert_eq!(token.transfer_validator(), None);
+            assert!(token.transfer(bob, 100).is_ok());
+
+            assert!(token.set_transfer_validator(Some(validator)).is_ok());
+            assert_eq!(token.transfer_validator(), Some(validator));
+
+            // Failing local checks reject before the validator would be called
+            assert_eq!(token.transfer(bob, 10_000), Err(Error::InsufficientBalance));
+
+            assert!(token.set_transfer_validator(None).is_ok());
+            assert!(token.transfer(bob, 100).is_ok());
+            assert_eq!(token.balance_of(bob), 200);
+
+            test::set_caller(bob);
+            assert_eq!(token.set_transfer_validator(Some(bob)), Err(Error::Unauthorized));
+        }
+
+        #[ink::test]
+        fn stream_claims_accrue_over_time() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+            // Streams are escrowed at the token contract's own address
+            test::set_callee(H160::from([9u8; 20]));
+            set_timestamp(0);
+
+            let mut token = Token::new(10_000);
+            let bob = get_bob();
+
+            // 10 tokens per second for 100 seconds
+            let stream_id = token.create_stream(bob, 10, 100).unwrap();
+            assert_eq!(token.balance_of(accounts.alice), 9_000);
+
+            test::set_caller(bob);
+            set_timestamp(30_000);
+            assert_eq!(token.stream_claimable(stream_id), 300);
+            assert_eq!(token.withdraw_stream(stream_id), Ok(300));
+            assert_eq!(token.balance_of(bob), 300);
+            assert_eq!(token.withdraw_stream(stream_id), Ok(0));
+
+            // Past the end only the remainder is paid and the stream closes
+            set_timestamp(150_000);
+            assert_eq!(token.withdraw_stream(stream_id), Ok(700));
+            assert_eq!(token.balance_of(bob), 1_000);
+            assert_eq!(token.stream(stream_id), None);
+            assert_eq!(token.withdraw_stream(stream_id), Err(Error::StreamNotFound));
+        }
+
+        #[ink::test]
+        fn cancel_stream_refunds_remainder() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+            // Streams are escrowed at the token contract's own address
+            test::set_callee(H160::from([9u8; 20]));
+            set_timestamp(0);
+
+            let mut token = Token::new(10_000);
+            let bob = get_bob();
+
+            let stream_id = token.create_stream(bob, 10, 100).unwrap();
+            assert_eq!(token.create_stream(bob, 0, 100), Err(Error::ZeroAmount));
+
+            set_timestamp(40_000);
+            test::set_caller(bob);
+            assert_eq!(token.cancel_stream(stream_id), Err(Error::Unauthorized));
+
+            test::set_caller(accounts.alice);
+            assert!(token.cancel_stream(stream_id).is_ok());
+            assert_eq!(token.balance_of(bob), 400);
+            assert_eq!(token.balance_of(accounts.alice), 9_600);
+            assert_eq!(token.stream(stream_id), None);
+        }
+
+        #[ink::test]
+        fn stream_payouts_follow_transfer_policy() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+            test::set_callee(H160::from([9u8; 20]));
+            set_timestamp(0);
+
+            let mut token = Token::new(10_000);
+            let bob = get_bob();
+            let first = token.create_stream(bob, 10, 100).unwrap();
+            let second = token.create_stream(bob, 10, 100).unwrap();
+
+            // Accrued claims below the minimum transfer still pay out
+            assert!(token.set_min_transfer_whole(1).is_ok());
+            set_timestamp(10_000);
+            test::set_caller(bob);
+            assert_eq!(token.withdraw_stream(first), Ok(100));
+            assert_eq!(token.balance_of(bob), 100);
+
+            // A blacklisted recipient is refused, but the sender still gets the unstreamed refund
+            test::set_caller(accounts.alice);
+            assert!(token.blacklist_address(bob).is_ok());
+            assert_eq!(token.create_stream(bob, 1, 10), Err(Error::Blacklisted));
+            set_timestamp(40_000);
+            assert!(token.cancel_stream(second).is_ok());
+            assert_eq!(token.balance_of(accounts.alice), 8_000 + 600);
+            assert_eq!(token.balance_of(bob), 100);
+
+            // The accrued amount stays claimable and stops growing
+            set_timestamp(90_000);
+            assert_eq!(token.stream_claimable(second), 400);
+            test::set_caller(bob);
+            assert_eq!(token.withdraw_stream(second), Err(Error::Blacklisted));
+            assert_eq!(token.withdraw_stream(first), Err(Error::Blacklisted));
+            assert_eq!(token.balance_of(bob), 100);
+
+            test::set_caller(accounts.alice);
+            assert!(token.remove_from_blacklist(bob).is_ok());
+            test::set_caller(bob);
+            assert_eq!(token.withdraw_stream(second), Ok(400));
+            assert_eq!(token.stream(second), None);
+            assert_eq!(token.balance_of(bob), 500);
+        }
+
+        #[ink::test]
+        fn stream_escrow_rebases_with_holdings() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+            test::set_callee(H160::from([9u8; 20]));
+            set_timestamp(0);
+
+            let mut token = Token::new(10_000);
+            let bob = get_bob();
+            let stream_id = token.create_stream(bob, 10, 100).unwrap();
+
+            // Halving the supply halves the escrow, and the final claim still settles
+            assert!(token.rebase(5_000).is_ok());
+            set_timestamp(100_000);
+            test::set_caller(bob);
+            assert_eq!(token.withdraw_stream(stream_id), Ok(500));
+            assert_eq!(token.balance_of(bob), 500);
+            assert_eq!(token.stream(stream_id), None);
+        }
+
+        #[ink::test]
+        fn transfer_and_call_to_account_skips_callback() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+
+            // Accounts without code get a plain transfer, even with empty callback data
+            assert!(token.transfer_and_call(bob, 100, Vec::new()).is_ok());
+            assert_eq!(token.balance_of(bob), 100);
+            assert_eq!(token.balance_of(accounts.alice), 900);
+
+            // The transfer checks still apply
+            assert_eq!(token.transfer_and_call(bob, 10_000, vec![1, 2, 3]), Err(Error::InsufficientBalance));
+        }
+
+        #[ink::test]
+        fn frozen_account_receives_but_cannot_send() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+
+            assert!(!token.is_frozen(bob));
+            assert!(token.freeze(bob).is_ok());
+            assert!(token.is_frozen(bob));
+
+            let events = test::recorded_events().collect::<Vec<_>>();
+            let frozen = <AccountFrozen as scale::Decode>::decode(&mut &events[events.len() - 1].data[..])
+                .expect("invalid AccountFrozen event data");
+            assert_eq!(frozen.account, bob);
+
+            // Incoming settlements still land
+            assert!(token.transfer(bob, 100).is_ok());
+            assert_eq!(token.balance_of(bob), 100);
+
+            test::set_caller(bob);
+            assert_eq!(token.transfer(accounts.alice, 50), Err(Error::AccountFrozen));
+            assert_eq!(token.transferable_balance(bob), 0);
+            assert_eq!(token.unfreeze(bob), Err(Error::Unauthorized));
+
+            test::set_caller(accounts.alice);
+            assert!(token.unfreeze(bob).is_ok());
+            assert!(!token.is_frozen(bob));
+
+            test::set_caller(bob);
+            assert!(token.transfer(accounts.alice, 50).is_ok());
+        }
+
+        #[ink::test]
+        fn blacklist_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+
+            assert!(!token.is_blacklisted(bob));
+            assert!(token.blacklist_address(bob).is_ok());
+            assert!(token.is_blacklisted(bob));
+
+            let result = token.transfer(bob, 100);
+            assert_eq!(result, Err(Error::Blacklisted));
+        }
+
+        #[ink::test]
+        fn batch_transfer_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+            let charlie = get_charlie();
+
+            let recipients = vec![
+                (bob, 100),
+                (charlie, 200),
+            ];
+
+            assert!(token.batch_transfer(recipients).is_ok());
+            assert_eq!(token.balance_of(accounts.alice), 700);
+            assert_eq!(token.balance_of(bob), 100);
+            assert_eq!(token.balance_of(charlie), 200);
+        }
+
+        #[ink::test]
+        fn batch_transfer_is_all_or_nothing() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+            let charlie = get_charlie();
+
+            // The total exceeds the balance even though the first entries fit
+            let result = token.batch_transfer(vec![(bob, 400), (charlie, 400), (bob, 300)]);
+            assert_eq!(result, Err(Error::InsufficientBalance));
+            assert_eq!(token.balance_of(bob), 0);
+            assert_eq!(token.balance_of(charlie), 0);
+            assert_eq!(token.balance_of(accounts.alice), 1000);
+
+            // A blacklisted recipient later in the list stops the whole batch
+            assert!(token.blacklist_address(charlie).is_ok());
+            let result = token.batch_transfer(vec![(bob, 100), (charlie, 100)]);
+            assert_eq!(result, Err(Error::Blacklisted));
+            assert_eq!(token.balance_of(bob), 0);
+
+            assert!(token.pause().is_ok());
+            assert_eq!(token.batch_transfer(vec![(bob, 100)]), Err(Error::Paused));
+            assert_eq!(token.balance_of(accounts.alice), 1000);
+        }
+
+        #[ink::test]
+        fn batch_transfer_equal_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+            let charlie = get_charlie();
+
+            assert!(token.batch_transfer_equal(vec![bob, charlie], 150).is_ok());
+            assert_eq!(token.balance_of(bob), 150);
+            assert_eq!(token.balance_of(charlie), 150);
+            assert_eq!(token.balance_of(accounts.alice), 700);
+        }
+
+        #[ink::test]
+        fn batch_transfer_equal_over_budget_fails() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+            let charlie = get_charlie();
+
+            let result = token.batch_transfer_equal(vec![bob, charlie, bob], 400);
+            assert_eq!(result, Err(Error::InsufficientBalance));
+            assert_eq!(token.balance_of(bob), 0);
+            assert_eq!(token.balance_of(charlie), 0);
+
+            let result = token.batch_transfer_equal(vec![bob, charlie], Balance::MAX);
+            assert_eq!(result, Err(Error::Overflow));
+            assert_eq!(token.balance_of(accounts.alice), 1000);
+        }
+
+        #[ink::test]
+        fn only_owner_can_pause() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+
+            test::set_caller(bob);
+            let result = token.pause();
+            assert_eq!(result, Err(Error::Unauthorized));
+        }
+
+        #[ink::test]
+        fn operator_can_pause_and_blacklist() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+            let charlie = get_charlie();
+            assert!(token.set_operator(Some(bob)).is_ok());
+            assert_eq!(token.operator(), Some(bob));
+
+            test::set_caller(bob);
+            assert!(token.pause().is_ok());
+            assert!(token.is_paused());
+            assert!(token.unpause().is_ok());
+            assert!(token.blacklist_address(charlie).is_ok());
+            assert!(token.is_blacklisted(charlie));
+
+            // Owner-only powers stay with the owner
+            assert_eq!(token.mint(100), Err(Error::Unauthorized));
+            assert_eq!(token.mint_to(bob, 100), Err(Error::Unauthorized));
+            assert_eq!(token.add_minter(bob), Err(Error::Unauthorized));
+            assert_eq!(token.set_operator(Some(charlie)), Err(Error::Unauthorized));
+
+            // Clearing the operator revokes its powers
+            test::set_caller(accounts.alice);
+            assert!(token.set_operator(None).is_ok());
+            test::set_caller(bob);
+            assert_eq!(token.pause(), Err(Error::Unauthorized));
+        }
+
+        #[ink::test]
+        fn two_step_ownership_transfer_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+            let charlie = get_charlie();
+
+            assert!(token.transfer_ownership(bob).is_ok());
+            assert_eq!(token.pending_owner(), Some(bob));
+            assert_eq!(token.owner(), accounts.alice);
+
+            // A stranger cannot accept
+            test::set_caller(charlie);
+            assert_eq!(token.accept_ownership(), Err(Error::Unauthorized));
+
+            test::set_caller(bob);
+            assert!(token.accept_ownership().is_ok());
+            assert_eq!(token.owner(), bob);
+            assert_eq!(token.pending_owner(), None);
+
+            let events = test::recorded_events().collect::<Vec<_>>();
+            let event = <OwnershipTransferred as scale::Decode>::decode(&mut &events[events.len() - 1].data[..])
+                .expect("invalid OwnershipTransferred event data");
+            assert_eq!(event.previous, accounts.alice);
+            assert_eq!(event.new, bob);
+
+            // The old owner has lost its powers
+            test::set_caller(accounts.alice);
+            assert_eq!(token.pause(), Err(Error::Unauthorized));
+            assert_eq!(token.transfer_ownership(accounts.alice), Err(Error::Unauthorized));
+        }
+
+        #[ink::test]
+        fn renounce_ownership_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+            assert!(token.set_operator(Some(bob)).is_ok());
+            assert!(token.transfer_ownership(bob).is_ok());
+
+            test::set_caller(bob);
+            assert_eq!(token.renounce_ownership(), Err(Error::Unauthorized));
+
+            test::set_caller(accounts.alice);
+            assert!(token.renounce_ownership().is_ok());
+            assert_eq!(token.owner(), H160::zero());
+            assert_eq!(token.operator(), None);
+            assert_eq!(token.pending_owner(), None);
+
+            let events = test::recorded_events().collect::<Vec<_>>();
+            let event = <OwnershipTransferred as scale::Decode>::decode(&mut &events[events.len() - 1].data[..])
+                .expect("invalid OwnershipTransferred event data");
+            assert_eq!(event.previous, accounts.alice);
+            assert_eq!(event.new, H160::zero());
+
+            assert_eq!(token.pause(), Err(Error::Unauthorized));
+            assert_eq!(token.blacklist_address(bob), Err(Error::Unauthorized));
+            assert_eq!(token.mint(100), Err(Error::Unauthorized));
+
+            // Neither the former operator nor the former pending owner can take over
+            test::set_caller(bob);
+            assert_eq!(token.pause(), Err(Error::Unauthorized));
+            assert_eq!(token.accept_ownership(), Err(Error::Unauthorized));
+        }
+
+        #[ink::test]
+        fn renounce_requires_revoked_roles() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+            let charlie = get_charlie();
+            assert!(token.add_minter(bob).is_ok());
+            assert!(token.add_minter(bob).is_ok());
+            assert!(token.add_finance_admin(charlie).is_ok());
+
+            assert_eq!(token.renounce_ownership(), Err(Error::RolesStillAssigned));
+            assert!(token.remove_minter(bob).is_ok());
+            assert_eq!(token.renounce_ownership(), Err(Error::RolesStillAssigned));
+            assert!(token.remove_finance_admin(charlie).is_ok());
+            assert!(token.renounce_ownership().is_ok());
+
+            // The revoked minter cannot mint once nobody can restore the role
+            test::set_caller(bob);
+            assert_eq!(token.mint_to(bob, 100), Err(Error::Unauthorized));
+            assert_eq!(token.total_supply(), 1000);
+        }
+
+        #[ink::test]
+        fn renounce_requires_lifted_restrictions() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+            let transfer = ink::selector_bytes!("transfer");
+
+            assert!(token.pause().is_ok());
+            assert_eq!(token.renounce_ownership(), Err(Error::RestrictionsActive));
+            assert!(token.unpause().is_ok());
+
+            assert!(token.set_selector_blocked(transfer, true).is_ok());
+            assert!(token.set_selector_blocked(transfer, true).is_ok());
+            assert_eq!(token.renounce_ownership(), Err(Error::RestrictionsActive));
+            assert!(token.set_selector_blocked(transfer, false).is_ok());
+
+            assert!(token.freeze(bob).is_ok());
+            assert_eq!(token.renounce_ownership(), Err(Error::RestrictionsActive));
+            assert!(token.unfreeze(bob).is_ok());
+
+            assert!(token.renounce_ownership().is_ok());
+            assert_eq!(token.owner(), H160::zero());
+        }
+
+        #[ink::test]
+        fn only_owner_can_blacklist() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+            let charlie = get_charlie();
+
+            test::set_caller(bob);
+            let result = token.blacklist_address(charlie);
+            assert_eq!(result, Err(Error::Unauthorized));
+        }
+
+        #[ink::test]
+        fn mint_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+
+            assert_eq!(token.total_supply(), 1000);
+            assert_eq!(token.balance_of(accounts.alice), 1000);
+
+            assert!(token.mint(500).is_ok());
+
+            assert_eq!(token.total_supply(), 1500);
+            assert_eq!(token.balance_of(accounts.alice), 1500);
+        }
+
+        #[ink::test]
+        fn only_owner_can_mint() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+
+            test::set_caller(bob);
+            assert_eq!(token.mint(500), Err(Error::Unauthorized));
+            assert_eq!(
+                token.batch_mint_lenient(vec![(bob, 100), (bob, 200)]),
+                vec![Err(Error::Unauthorized), Err(Error::Unauthorized)]
+            );
+            assert_eq!(token.balance_of(bob), 0);
+            assert_eq!(token.total_supply(), 1000);
+
+            test::set_caller(accounts.alice);
+            assert!(token.mint(500).is_ok());
+            assert_eq!(token.total_supply(), 1500);
+        }
+
+        #[ink::test]
+        fn minter_role_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+            let charlie = get_charlie();
+
+            assert!(token.add_minter(bob).is_ok());
+            assert!(token.is_minter(bob));
+            let events = test::recorded_events().collect::<Vec<_>>();
+            let event = <MinterAdded as scale::Decode>::decode(&mut &events[events.len() - 1].data[..])
+                .expect("invalid MinterAdded event data");
+            assert_eq!(event.account, bob);
+
+            test::set_caller(bob);
+            assert!(token.mint(100).is_ok());
+            assert!(token.mint_to(charlie, 50).is_ok());
+            assert_eq!(token.balance_of(bob), 100);
+            assert_eq!(token.balance_of(charlie), 50);
+            assert_eq!(token.total_supply(), 1150);
+
+            test::set_caller(accounts.alice);
+            assert!(token.remove_minter(bob).is_ok());
+            assert!(!token.is_minter(bob));
+            let events = test::recorded_events().collect::<Vec<_>>();
+            let event = <MinterRemoved as scale::Decode>::decode(&mut &events[events.len() - 1].data[..])
+                .expect("invalid MinterRemoved event data");
+            assert_eq!(event.account, bob);
+
+            test::set_caller(bob);
+            assert_eq!(token.mint(100), Err(Error::Unauthorized));
+            assert_eq!(token.mint_to(charlie, 50), Err(Error::Unauthorized));
+            assert_eq!(token.total_supply(), 1150);
+        }
+
+        #[ink::test]
+        fn only_owner_can_manage_minters() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            assert!(token.add_minter(get_bob()).is_ok());
+
+            // Minters cannot grant or revoke the role themselves
+            test::set_caller(get_bob());
+            assert_eq!(token.add_minter(get_charlie()), Err(Error::Unauthorized));
+            assert_eq!(token.remove_minter(get_bob()), Err(Error::Unauthorized));
+        }
+
+        #[ink::test]
+        fn mint_to_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+
+            assert!(token.mint_to(bob, 300).is_ok());
+            assert_eq!(token.balance_of(bob), 300);
+            assert_eq!(token.balance_of(accounts.alice), 1000);
+            assert_eq!(token.total_supply(), 1300);
+
+            let events = test::recorded_events().collect::<Vec<_>>();
+            let event = decode_transfer(&events[events.len() - 2]);
+            assert_eq!(event.from, None);
+            assert_eq!(event.to, Some(bob));
+            assert_eq!(event.value, 300);
+
+            assert_eq!(token.mint_to(bob, 0), Err(Error::ZeroAmount));
+        }
+
+        #[ink::test]
+        fn mint_to_rejects_bad_calls() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+            let charlie = get_charlie();
+
+            assert!(token.blacklist_address(charlie).is_ok());
+            assert_eq!(token.mint_to(charlie, 100), Err(Error::Blacklisted));
+
+            test::set_caller(bob);
+            assert_eq!(token.mint_to(bob, 100), Err(Error::Unauthorized));
+            assert_eq!(token.balance_of(bob), 0);
+            assert_eq!(token.total_supply(), 1000);
+        }
+
+        #[ink::test]
+        fn mint_overflow_fails() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(Balance::MAX - 10);
+
+            assert!(token.mint(10).is_ok());
+            assert_eq!(token.total_supply(), Balance::MAX);
+
+            assert_eq!(token.mint(1), Err(Error::Overflow));
+            assert_eq!(token.mint_to(get_bob(), 1), Err(Error::Overflow));
+            assert_eq!(token.total_supply(), Balance::MAX);
+            assert_eq!(token.balance_of(accounts.alice), Balance::MAX);
+        }
+
+        #[ink::test]
+        fn transfer_overflow_fails() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+            token.balances.insert(bob, &Balance::MAX);
+
+            assert_eq!(token.transfer(bob, 1), Err(Error::Overflow));
+            assert_eq!(token.balance_of(accounts.alice), 1000);
+            assert_eq!(token.balance_of(bob), Balance::MAX);
+
+            // Self-transfers never overflow
+            test::set_caller(bob);
+            assert!(token.transfer(bob, Balance::MAX).is_ok());
+            assert_eq!(token.balance_of(bob), Balance::MAX);
+        }
+
+        #[ink::test]
+        fn increase_allowance_overflow_fails() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+
+            assert!(token.approve(bob, Balance::MAX).is_ok());
+            assert_eq!(token.increase_allowance(bob, 1), Err(Error::Overflow));
+            assert_eq!(token.allowance(accounts.alice, bob), Balance::MAX);
+        }
+
+        #[ink::test]
+        fn increase_allowance_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+
+            assert!(token.approve(bob, 100).is_ok());
+            assert_eq!(token.allowance(accounts.alice, bob), 100);
+
+            assert!(token.increase_allowance(bob, 50).is_ok());
+            assert_eq!(token.allowance(accounts.alice, bob), 150);
+        }
+
+        #[ink::test]
+        fn decrease_allowance_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+
+            assert!(token.approve(bob, 100).is_ok());
+            assert_eq!(token.allowance(accounts.alice, bob), 100);
+
+            assert!(token.decrease_allowance(bob, 30).is_ok());
+            assert_eq!(token.allowance(accounts.alice, bob), 70);
+        }
+
+        #[ink::test]
+        fn decrease_allowance_insufficient_fails() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+
+            assert!(token.approve(bob, 50).is_ok());
+
+            let result = token.decrease_allowance(bob, 100);
+            assert_eq!(result, Err(Error::InsufficientAllowance));
+        }
+
+        #[ink::test]
+        fn approval_events_report_final_allowance() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+            let before = test::recorded_events().count();
+
+            assert!(token.approve(bob, 100).is_ok());
+            assert!(token.increase_allowance(bob, 50).is_ok());
+            assert!(token.decrease_allowance(bob, 30).is_ok());
+
+            let events = test::recorded_events().skip(before).collect::<Vec<_>>();
+            assert_eq!(events.len(), 3);
+            let values = events.iter().map(|event| decode_approval(event).value).collect::<Vec<_>>();
+            assert_eq!(values, vec![100, 150, 120]);
+
+            let event = decode_approval(&events[0]);
+            assert_eq!(event.owner, accounts.alice);
+            assert_eq!(event.spender, bob);
+
+            // Unchanged allowances still emit
+            assert!(token.approve(bob, 120).is_ok());
+            assert!(token.increase_allowance(bob, 0).is_ok());
+            let events = test::recorded_events().skip(before + 3).collect::<Vec<_>>();
+            assert_eq!(events.len(), 2);
+            assert!(events.iter().all(|event| decode_approval(event).value == 120));
+        }
+
+        #[ink::test]
+        fn min_approval_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+
+            assert!(token.set_min_approval(100).is_ok());
+            assert_eq!(token.min_approval(), 100);
+
+            assert_eq!(token.approve(bob, 99), Err(Error::BelowMinimum));
+            assert!(token.approve(bob, 100).is_ok());
+            assert!(token.approve(bob, 150).is_ok());
+            assert_eq!(token.allowance(accounts.alice, bob), 150);
+
+            // Zero is always allowed to revoke
+            assert!(token.approve(bob, 0).is_ok());
+            assert_eq!(token.allowance(accounts.alice, bob), 0);
+
+            assert_eq!(token.increase_allowance(bob, 50), Err(Error::BelowMinimum));
+            assert!(token.increase_allowance(bob, 100).is_ok());
+            assert_eq!(token.allowance(accounts.alice, bob), 100);
+        }
+
+        #[ink::test]
+        fn max_approval_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+
+            assert!(token.set_max_approval(Some(500)).is_ok());
+            assert_eq!(token.max_approval(), Some(500));
+
+            assert!(token.approve(bob, 500).is_ok());
+            assert_eq!(token.allowance(accounts.alice, bob), 500);
+
+            assert_eq!(token.approve(bob, 501), Err(Error::ApprovalTooLarge));
+            assert_eq!(token.approve(bob, Balance::MAX), Err(Error::ApprovalTooLarge));
+            assert_eq!(token.increase_allowance(bob, 1), Err(Error::ApprovalTooLarge));
+            assert_eq!(token.allowance(accounts.alice, bob), 500);
+
+            // Removing the cap allows infinite approvals again
+            assert!(token.set_max_approval(None).is_ok());
+            assert!(token.approve(bob, Balance::MAX).is_ok());
+        }
+
+        #[ink::test]
+        fn low_allowance_event_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+            let charlie = get_charlie();
+
+            assert!(token.set_low_allowance_threshold(50).is_ok());
+            assert!(token.approve(bob, 100).is_ok());
+
+            // Staying above the threshold only emits the transfer events
+            test::set_caller(bob);
+            let before = test::recorded_events().count();
+            assert!(token.transfer_from(accounts.alice, charlie, 40).is_ok());
+            assert_eq!(test::recorded_events().count(), before + 2);
+
+            // Crossing below the threshold also emits LowAllowance
+            assert!(token.transfer_from(accounts.alice, charlie, 20).is_ok());
+            let events = test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(events.len(), before + 5);
+
+            let event = <LowAllowance as scale::Decode>::decode(&mut &events[before + 4].data[..])
+                .expect("invalid LowAllowance event data");
+            assert_eq!(event.owner, accounts.alice);
+            assert_eq!(event.spender, bob);
+            assert_eq!(event.remaining, 40);
+        }
+
+        #[ink::test]
+        fn min_transfer_scales_with_decimals() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1_000_000);
+            let bob = get_bob();
+
+            assert!(token.set_min_transfer_whole(2).is_ok());
+
+            token.decimals = 0;
+            assert_eq!(token.min_transfer(), 2);
+            assert_eq!(token.transfer(bob, 1), Err(Error::BelowMinimum));
+            assert!(token.transfer(bob, 2).is_ok());
+
+            token.decimals = 3;
+            assert_eq!(token.min_transfer(), 2000);
+            assert_eq!(token.transfer(bob, 1999), Err(Error::BelowMinimum));
+            assert!(token.transfer(bob, 2000).is_ok());
+            assert_eq!(token.balance_of(bob), 2002);
+
+            // Scaling that overflows saturates instead of wrapping
+            token.decimals = 40;
+            assert_eq!(token.min_transfer(), Balance::MAX);
+
+            // Zero disables the minimum
+            assert!(token.set_min_transfer_whole(0).is_ok());
+            assert!(token.transfer(bob, 1).is_ok());
+        }
+
+        #[ink::test]
+        fn transfer_capped_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+
+            assert!(token.transfer(bob, 100).is_ok());
+
+            // Landing exactly on the cap is allowed
+            assert!(token.transfer_capped(bob, 100, 200).is_ok());
+            assert_eq!(token.balance_of(bob), 200);
+
+            let result = token.transfer_capped(bob, 1, 200);
+            assert_eq!(result, Err(Error::RecipientCapExceeded));
+            assert_eq!(token.balance_of(bob), 200);
+            assert_eq!(token.balance_of(accounts.alice), 800);
+        }
+
+        #[ink::test]
+        fn transfer_before_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+
+            set_timestamp(1000);
+            // The deadline itself is still valid
+            assert!(token.transfer_before(bob, 100, 1000).is_ok());
+            assert_eq!(token.balance_of(bob), 100);
+
+            set_timestamp(1001);
+            assert_eq!(token.transfer_before(bob, 100, 1000), Err(Error::Expired));
+            assert_eq!(token.balance_of(bob), 100);
+            assert_eq!(token.balance_of(accounts.alice), 900);
+        }
+
+        #[ink::test]
+        fn total_approved_out_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+            let charlie = get_charlie();
+
+            assert_eq!(token.total_approved_out(accounts.alice), 0);
+
+            assert!(token.approve(bob, 100).is_ok());
+            assert!(token.approve(charlie, 250).is_ok());
+            assert_eq!(token.spenders_of(accounts.alice), vec![bob, charlie]);
+            assert_eq!(token.total_approved_out(accounts.alice), 350);
+
+            // Revoking drops the spender from the tracked list
+            assert!(token.approve(bob, 0).is_ok());
+            assert_eq!(token.spenders_of(accounts.alice), vec![charlie]);
+            assert_eq!(token.total_approved_out(accounts.alice), 250);
+        }
+
+        #[ink::test]
+        fn infinite_allowance_is_not_decremented() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+            let charlie = get_charlie();
+            assert!(token.approve(bob, Balance::MAX).is_ok());
+
+            test::set_caller(bob);
+            assert!(token.transfer_from(accounts.alice, charlie, 100).is_ok());
+            assert_eq!(token.transfer_from_detailed(accounts.alice, charlie, 200), Ok((0, Balance::MAX)));
+            assert_eq!(token.allowance(accounts.alice, bob), Balance::MAX);
+            assert_eq!(token.balance_of(charlie), 300);
+
+            // Finite allowances still go down
+            test::set_caller(accounts.alice);
+            assert!(token.approve(bob, 500).is_ok());
+            test::set_caller(bob);
+            assert!(token.transfer_from(accounts.alice, charlie, 100).is_ok());
+            assert_eq!(token.allowance(accounts.alice, bob), 400);
+        }
+
+        #[ink::test]
+        fn allowance_summary_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            assert_eq!(token.allowance_summary(accounts.alice), (0, 0));
+
+            assert!(token.approve(get_bob(), 100).is_ok());
+            assert!(token.approve(get_charlie(), 250).is_ok());
+            assert_eq!(token.allowance_summary(accounts.alice), (2, 350));
+
+            assert!(token.approve(get_bob(), 0).is_ok());
+            assert_eq!(token.allowance_summary(accounts.alice), (1, 250));
+        }
+
+        #[ink::test]
+        fn transfer_from_detailed_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+            let charlie = get_charlie();
 
-            // Check if sender or recipient is blacklisted
-            if self.is_blacklisted(*from) || self.is_blacklisted(*to) {
-                return Err(Error::Blacklisted);
-            }
+            assert!(token.approve(bob, 100).is_ok());
 
-            let from_balance = self.balance_of(*from);
-            if from_balance < value {
-                return Err(Error::InsufficientBalance);
-            }
+            test::set_caller(bob);
+            assert_eq!(token.transfer_from_detailed(accounts.alice, charlie, 30), Ok((30, 70)));
+            assert_eq!(token.transfer_from_detailed(accounts.alice, charlie, 70), Ok((70, 0)));
+            assert_eq!(
+                token.transfer_from_detailed(accounts.alice, charlie, 1),
+                Err(Error::InsufficientAllowance)
+            );
+            assert_eq!(token.balance_of(charlie), 100);
+        }
 
-            self.balances.insert(from, &from_balance.saturating_sub(value));
-            let to_balance = self.balance_of(*to);
-            self.balances.insert(to, &to_balance.saturating_add(value));
+        #[ink::test]
+        fn contract_denylist_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
 
-            // self.env().emit_event(Transfer {
-            //     from: Some(*from),
-            //     to: Some(*to),
-            //     value,
-            // });
+            let mut token = Token::new(1000);
+            let bob = get_bob();
 
-            Ok(())
-        }
-    }
+            assert!(!token.is_contract_denied(bob));
+            assert!(token.deny_contract(bob).is_ok());
+            assert!(token.is_contract_denied(bob));
 
-    #[cfg(test)]
-    mod tests {
-        use super::*;
-        use ink::env::test;
+            // Bob has no code, so the denylist does not apply to him
+            assert!(!token.is_contract(bob));
+            assert!(token.transfer(bob, 100).is_ok());
 
-        fn get_default_accounts() -> test::DefaultAccounts {
-            test::default_accounts()
-        }
+            assert!(token.allow_contract(bob).is_ok());
+            assert!(!token.is_contract_denied(bob));
 
-        fn get_bob() -> H160 {
-            H160::from([2u8; 20])
+            test::set_caller(bob);
+            assert_eq!(token.deny_contract(accounts.alice), Err(Error::Unauthorized));
         }
 
-        fn get_charlie() -> H160 {
-            H160::from([3u8; 20])
+        #[ink::test]
+        fn finance_admin_can_move_treasury_funds() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+            let charlie = get_charlie();
+
+            assert_eq!(token.treasury(), accounts.alice);
+            assert!(token.add_finance_admin(bob).is_ok());
+            assert!(token.is_finance_admin(bob));
+
+            test::set_caller(bob);
+            assert!(token.transfer_from_treasury(charlie, 300).is_ok());
+            assert_eq!(token.balance_of(accounts.alice), 700);
+            assert_eq!(token.balance_of(charlie), 300);
+
+            // A removed admin can no longer move treasury funds
+            test::set_caller(accounts.alice);
+            assert!(token.remove_finance_admin(bob).is_ok());
+
+            test::set_caller(bob);
+            assert_eq!(token.transfer_from_treasury(charlie, 100), Err(Error::Unauthorized));
+            assert_eq!(token.add_finance_admin(bob), Err(Error::Unauthorized));
+            assert_eq!(token.balance_of(accounts.alice), 700);
         }
 
         #[ink::test]
-        fn new_works() {
-            let token = Token::new(1000);
-            assert_eq!(token.total_supply(), 1000);
+        fn treasury_must_accept_the_role() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+            let charlie = get_charlie();
+            assert!(token.transfer(charlie, 500).is_ok());
+            assert!(token.add_finance_admin(bob).is_ok());
+
+            // Pointing the treasury at a holder does not expose their tokens
+            assert!(token.set_treasury(charlie).is_ok());
+            assert_eq!(token.treasury(), accounts.alice);
+            assert_eq!(token.pending_treasury(), Some(charlie));
+
+            test::set_caller(bob);
+            assert_eq!(token.accept_treasury(), Err(Error::Unauthorized));
+            assert!(token.transfer_from_treasury(bob, 100).is_ok());
+            assert_eq!(token.balance_of(charlie), 500);
+            assert_eq!(token.balance_of(accounts.alice), 400);
+
+            // Once charlie accepts, the treasury is theirs to fund
+            test::set_caller(charlie);
+            assert!(token.accept_treasury().is_ok());
+            assert_eq!(token.treasury(), charlie);
+            assert_eq!(token.pending_treasury(), None);
+
+            // The token contract itself can be made the treasury directly
+            test::set_caller(accounts.alice);
+            let contract = H160::from([9u8; 20]);
+            test::set_callee(contract);
+            assert!(token.set_treasury(contract).is_ok());
+            assert_eq!(token.treasury(), contract);
         }
 
         #[ink::test]
-        fn balance_works() {
+        fn is_contract_address_is_false_for_accounts() {
             let accounts = get_default_accounts();
             test::set_caller(accounts.alice);
 
             let token = Token::new(1000);
-            let bob = get_bob();
 
-            assert_eq!(token.balance_of(accounts.alice), 1000);
-            assert_eq!(token.balance_of(bob), 0);
+            assert!(!token.is_contract_address(accounts.alice));
+            assert!(!token.is_contract_address(get_bob()));
+            assert!(!token.is_contract_address(H160::zero()));
         }
 
         #[ink::test]
-        fn transfer_works() {
+        fn self_freeze_works() {
             let accounts = get_default_accounts();
             test::set_caller(accounts.alice);
 
             let mut token = Token::new(1000);
             let bob = get_bob();
 
-            assert_eq!(token.balance_of(accounts.alice), 1000);
-            assert_eq!(token.balance_of(bob), 0);
-
             assert!(token.transfer(bob, 100).is_ok());
 
-            assert_eq!(token.balance_of(accounts.alice), 900);
-            assert_eq!(token.balance_of(bob), 100);
+            // Bob freezes himself and can no longer send, but can still receive
+            test::set_caller(bob);
+            assert!(token.freeze_self().is_ok());
+            assert!(token.is_self_frozen(bob));
+            assert_eq!(token.transfer(accounts.alice, 10), Err(Error::AccountFrozen));
+
+            test::set_caller(accounts.alice);
+            assert!(token.transfer(bob, 50).is_ok());
+            assert_eq!(token.balance_of(bob), 150);
+
+            // Unfreezing needs a request and then the full delay
+            test::set_caller(bob);
+            set_timestamp(1000);
+            assert_eq!(token.unfreeze_self(), Err(Error::UnfreezeNotReady));
+            assert!(token.request_unfreeze().is_ok());
+            assert_eq!(token.unfreeze_available_at(bob), Some(1000 + SELF_UNFREEZE_DELAY));
+            assert_eq!(token.unfreeze_self(), Err(Error::UnfreezeNotReady));
+
+            set_timestamp(1000 + SELF_UNFREEZE_DELAY);
+            assert!(token.unfreeze_self().is_ok());
+            assert!(!token.is_self_frozen(bob));
+            assert!(token.transfer(accounts.alice, 10).is_ok());
         }
 
         #[ink::test]
-        fn transfer_insufficient_balance_fails() {
-            let mut token = Token::new(100);
-            let bob = get_bob();
+        fn request_unfreeze_requires_freeze() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
 
-            let result = token.transfer(bob, 200);
-            assert_eq!(result, Err(Error::InsufficientBalance));
+            let mut token = Token::new(1000);
+
+            assert_eq!(token.request_unfreeze(), Err(Error::NotFrozen));
+            assert_eq!(token.unfreeze_self(), Err(Error::NotFrozen));
         }
 
         #[ink::test]
-        fn approve_works() {
+        fn config_works() {
             let accounts = get_default_accounts();
             test::set_caller(accounts.alice);
 
             let mut token = Token::new(1000);
             let bob = get_bob();
 
-            assert_eq!(token.allowance(accounts.alice, bob), 0);
-            assert!(token.approve(bob, 100).is_ok());
-            assert_eq!(token.allowance(accounts.alice, bob), 100);
+            assert!(token.pause().is_ok());
+            assert!(token.set_treasury(bob).is_ok());
+            test::set_caller(bob);
+            assert!(token.accept_treasury().is_ok());
+            test::set_caller(accounts.alice);
+            assert!(token.set_min_transfer_whole(2).is_ok());
+            assert!(token.set_cap(5000).is_ok());
+            assert!(token.set_min_approval(10).is_ok());
+            assert!(token.set_max_approval(Some(500)).is_ok());
+            assert!(token.set_low_allowance_threshold(20).is_ok());
+            assert!(token.set_native_fee(U256::from(7)).is_ok());
+            assert!(token.set_operator(Some(bob)).is_ok());
+            assert!(token.transfer_ownership(get_charlie()).is_ok());
+
+            let config = token.config();
+            assert_eq!(config.owner, accounts.alice);
+            assert_eq!(config.pending_owner, Some(get_charlie()));
+            assert_eq!(config.operator, Some(bob));
+            assert!(config.paused);
+            assert_eq!(config.treasury, bob);
+            assert_eq!(config.decimals, 18);
+            assert_eq!(config.min_transfer_whole, 2);
+            assert_eq!(config.max_supply, 5000);
+            assert_eq!(config.min_approval, 10);
+            assert_eq!(config.max_approval, Some(500));
+            assert_eq!(config.low_allowance_threshold, 20);
+            assert_eq!(config.native_fee, U256::from(7));
+            assert_eq!(config.fee_collector, accounts.alice);
+            assert_eq!(config.max_holders, None);
+            assert_eq!(config.transfer_validator, None);
         }
 
         #[ink::test]
-        fn transfer_from_works() {
+        fn transfer_with_native_fee_works() {
             let accounts = get_default_accounts();
             test::set_caller(accounts.alice);
+            // Fees are paid out of the token contract's own native balance
+            let contract = H160::from([9u8; 20]);
+            test::set_callee(contract);
 
             let mut token = Token::new(1000);
             let bob = get_bob();
             let charlie = get_charlie();
 
-            // Approve Bob to spend tokens
-            assert!(token.approve(bob, 100).is_ok());
-
-            // Set caller to Bob for transfer_from
+            assert!(token.set_native_fee(U256::from(100)).is_ok());
+            assert!(token.transfer(bob, 500).is_ok());
             test::set_caller(bob);
 
-            // Bob transfers from alice to Charlie
-            assert!(token.transfer_from(accounts.alice, charlie, 50).is_ok());
+            // Underpaying is rejected without moving tokens
+            test::set_value_transferred(U256::from(99));
+            let result = token.transfer_with_native_fee(charlie, 100);
+            assert_eq!(result, Err(Error::InsufficientFee));
+            assert_eq!(token.balance_of(charlie), 0);
+
+            // Paying exactly the fee forwards all of it to the collector
+            let native_balance =
+                |account: H160| test::get_account_balance::<DefaultEnvironment>(account).unwrap_or_default();
+            let collector_before = native_balance(accounts.alice);
+            let payer_before = native_balance(bob);
+            test::set_account_balance(contract, U256::from(100));
+            test::set_value_transferred(U256::from(100));
+            assert!(token.transfer_with_native_fee(charlie, 100).is_ok());
+            assert_eq!(token.balance_of(charlie), 100);
+            assert_eq!(native_balance(accounts.alice), collector_before + U256::from(100));
+            assert_eq!(native_balance(bob), payer_before);
+            assert_eq!(native_balance(contract), U256::zero());
+
+            // The token leg is a user transfer and the fee gets its own event
+            let events = test::recorded_events().collect::<Vec<_>>();
+            let fee_event = <NativeFeePaid as scale::Decode>::decode(&mut &events[events.len() - 1].data[..])
+                .expect("invalid NativeFeePaid event data");
+            assert_eq!(fee_event.payer, bob);
+            assert_eq!(fee_event.collector, accounts.alice);
+            assert_eq!(fee_event.amount, U256::from(100));
+            assert_eq!(decode_category(&events[events.len() - 2]).category, CATEGORY_USER);
+
+            // Overpaying refunds the excess to the payer
+            test::set_account_balance(contract, U256::from(150));
+            test::set_value_transferred(U256::from(150));
+            assert!(token.transfer_with_native_fee(charlie, 100).is_ok());
+            assert_eq!(token.balance_of(charlie), 200);
+            assert_eq!(token.balance_of(bob), 300);
+            assert_eq!(native_balance(accounts.alice), collector_before + U256::from(200));
+            assert_eq!(native_balance(bob), payer_before + U256::from(50));
+            assert_eq!(native_balance(contract), U256::zero());
+        }
 
-            // Check balances
-            assert_eq!(token.balance_of(accounts.alice), 950);
-            assert_eq!(token.balance_of(charlie), 50);
-            assert_eq!(token.allowance(accounts.alice, bob), 50);
+        #[ink::test]
+        fn fee_collector_timelock_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+            assert_eq!(token.fee_collector(), accounts.alice);
+            assert_eq!(token.execute_fee_collector(), Err(Error::NoFeeCollectorQueued));
+
+            set_timestamp(1000);
+            let before = test::recorded_events().count();
+            assert!(token.queue_fee_collector(bob).is_ok());
+            let executable_at = 1000 + FEE_COLLECTOR_DELAY;
+            assert_eq!(token.pending_fee_collector(), Some((bob, executable_at)));
+
+            let events = test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(events.len(), before + 1);
+            let event = <FeeCollectorQueued as scale::Decode>::decode(&mut &events[before].data[..])
+                .expect("invalid FeeCollectorQueued event data");
+            assert_eq!(event.collector, bob);
+            assert_eq!(event.executable_at, executable_at);
+
+            // Too early
+            set_timestamp(executable_at - 1);
+            assert_eq!(token.execute_fee_collector(), Err(Error::FeeCollectorNotReady));
+            assert_eq!(token.fee_collector(), accounts.alice);
+
+            set_timestamp(executable_at);
+            assert!(token.execute_fee_collector().is_ok());
+            assert_eq!(token.fee_collector(), bob);
+            assert_eq!(token.pending_fee_collector(), None);
+
+            let events = test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(events.len(), before + 2);
+            let event = <FeeCollectorChanged as scale::Decode>::decode(&mut &events[before + 1].data[..])
+                .expect("invalid FeeCollectorChanged event data");
+            assert_eq!(event.old_collector, accounts.alice);
+            assert_eq!(event.new_collector, bob);
         }
 
         #[ink::test]
-        fn burn_works() {
+        fn only_owner_can_change_fee_collector() {
             let accounts = get_default_accounts();
             test::set_caller(accounts.alice);
 
             let mut token = Token::new(1000);
+            assert!(token.queue_fee_collector(get_charlie()).is_ok());
 
-            assert!(token.burn(100).is_ok());
-            assert_eq!(token.balance_of(accounts.alice), 900);
-            assert_eq!(token.total_supply(), 900);
+            test::set_caller(get_bob());
+            assert_eq!(token.queue_fee_collector(get_bob()), Err(Error::Unauthorized));
+            assert_eq!(token.execute_fee_collector(), Err(Error::Unauthorized));
         }
 
         #[ink::test]
-        fn pause_works() {
+        fn holder_count_works() {
             let accounts = get_default_accounts();
             test::set_caller(accounts.alice);
 
             let mut token = Token::new(1000);
             let bob = get_bob();
+            assert_eq!(token.holder_count(), 1);
 
-            assert!(!token.is_paused());
-            assert!(token.pause().is_ok());
-            assert!(token.is_paused());
+            assert!(token.transfer(bob, 100).is_ok());
+            assert_eq!(token.holder_count(), 2);
 
-            let result = token.transfer(bob, 100);
-            assert_eq!(result, Err(Error::Paused));
+            test::set_caller(bob);
+            assert!(token.burn(100).is_ok());
+            assert_eq!(token.holder_count(), 1);
         }
 
         #[ink::test]
-        fn blacklist_works() {
+        fn max_holders_works() {
             let accounts = get_default_accounts();
             test::set_caller(accounts.alice);
 
             let mut token = Token::new(1000);
             let bob = get_bob();
+            let charlie = get_charlie();
 
-            assert!(!token.is_blacklisted(bob));
-            assert!(token.blacklist_address(bob).is_ok());
-            assert!(token.is_blacklisted(bob));
+            assert!(token.set_max_holders(Some(2)).is_ok());
+            assert_eq!(token.max_holders(), Some(2));
 
-            let result = token.transfer(bob, 100);
-            assert_eq!(result, Err(Error::Blacklisted));
+            // Filling up to the cap
+            assert!(token.transfer(bob, 100).is_ok());
+            assert_eq!(token.holder_count(), 2);
+
+            // A third holder is rejected, by transfer and by mint
+            assert_eq!(token.transfer(charlie, 100), Err(Error::HolderLimitReached));
+            assert_eq!(token.mint_to(charlie, 100), Err(Error::HolderLimitReached));
+            assert_eq!(token.balance_of(charlie), 0);
+
+            // Existing holders can still receive
+            assert!(token.transfer(bob, 100).is_ok());
+            assert_eq!(token.balance_of(bob), 200);
+
+            // Handing over a whole balance replaces a holder rather than adding one
+            test::set_caller(bob);
+            assert!(token.transfer(charlie, 200).is_ok());
+            assert_eq!(token.holder_count(), 2);
+
+            test::set_caller(accounts.alice);
+            assert!(token.set_max_holders(None).is_ok());
+            assert!(token.transfer(bob, 100).is_ok());
+            assert_eq!(token.holder_count(), 3);
+
+            test::set_caller(bob);
+            assert_eq!(token.set_max_holders(Some(1)), Err(Error::Unauthorized));
         }
 
         #[ink::test]
-        fn batch_transfer_works() {
+        fn circulating_supply_works() {
             let accounts = get_default_accounts();
             test::set_caller(accounts.alice);
 
             let mut token = Token::new(1000);
             let bob = get_bob();
             let charlie = get_charlie();
+            assert!(token.set_treasury(bob).is_ok());
+            assert!(token.transfer(bob, 400).is_ok());
+            assert!(token.transfer(charlie, 100).is_ok());
+            assert_eq!(token.circulating_supply(), 1000);
+
+            assert!(token.add_non_circulating(bob).is_ok());
+            assert!(token.add_non_circulating(bob).is_ok());
+            assert_eq!(token.non_circulating_accounts(), vec![bob]);
+            assert_eq!(token.circulating_supply(), 600);
+            assert_eq!(token.total_supply(), 1000);
 
-            let recipients = vec![
-                (bob, 100),
-                (charlie, 200),
-            ];
+            assert!(token.add_non_circulating(charlie).is_ok());
+            assert_eq!(token.circulating_supply(), 500);
 
-            assert!(token.batch_transfer(recipients).is_ok());
-            assert_eq!(token.balance_of(accounts.alice), 700);
-            assert_eq!(token.balance_of(bob), 100);
-            assert_eq!(token.balance_of(charlie), 200);
+            assert!(token.remove_non_circulating(bob).is_ok());
+            assert_eq!(token.non_circulating_accounts(), vec![charlie]);
+            assert_eq!(token.circulating_supply(), 900);
+
+            test::set_caller(bob);
+            assert_eq!(token.add_non_circulating(bob), Err(Error::Unauthorized));
+            assert_eq!(token.remove_non_circulating(charlie), Err(Error::Unauthorized));
         }
 
         #[ink::test]
-        fn only_owner_can_pause() {
+        fn rebase_scales_balances_proportionally() {
             let accounts = get_default_accounts();
             test::set_caller(accounts.alice);
 
             let mut token = Token::new(1000);
             let bob = get_bob();
+            assert!(token.transfer(bob, 250).is_ok());
+
+            // Rebasing up doubles every balance
+            assert!(token.rebase(2000).is_ok());
+            assert_eq!(token.total_supply(), 2000);
+            assert_eq!(token.balance_of(accounts.alice), 1500);
+            assert_eq!(token.balance_of(bob), 500);
+            assert_eq!(token.shares_of(bob), 250);
+
+            // Transfers move shares at the new rate and conserve the total supply
+            assert!(token.transfer(bob, 100).is_ok());
+            assert_eq!(token.balance_of(accounts.alice), 1400);
+            assert_eq!(token.balance_of(bob), 600);
+            assert_eq!(token.total_supply(), 2000);
+
+            // Rebasing down shrinks every balance
+            assert!(token.rebase(500).is_ok());
+            assert_eq!(token.balance_of(accounts.alice), 350);
+            assert_eq!(token.balance_of(bob), 150);
+            assert_eq!(token.total_supply(), 500);
+
+            // Mints and burns follow the current rate
+            assert!(token.mint(50).is_ok());
+            assert_eq!(token.balance_of(accounts.alice), 400);
+            assert_eq!(token.total_supply(), 550);
 
             test::set_caller(bob);
-            let result = token.pause();
-            assert_eq!(result, Err(Error::Unauthorized));
+            assert!(token.burn(150).is_ok());
+            assert_eq!(token.balance_of(bob), 0);
+            assert_eq!(token.shares_of(bob), 0);
+            assert_eq!(token.holder_count(), 1);
         }
 
         #[ink::test]
-        fn only_owner_can_blacklist() {
+        fn rebase_rounding_never_creates_tokens() {
             let accounts = get_default_accounts();
             test::set_caller(accounts.alice);
 
             let mut token = Token::new(1000);
             let bob = get_bob();
-            let charlie = get_charlie();
+            assert!(token.transfer(bob, 250).is_ok());
+
+            // One share is now worth a little over 3 tokens
+            assert!(token.rebase(3000).is_ok());
+            assert_eq!(token.total_supply(), 3000);
+            assert_eq!(token.balance_of(accounts.alice), 2250);
+            assert_eq!(token.balance_of(bob), 750);
+
+            // A transfer worth less than a share still moves one, charged to the sender
+            assert!(token.transfer(bob, 1).is_ok());
+            assert_eq!(token.shares_of(bob), 251);
+            assert_eq!(token.balance_of(accounts.alice), 2247);
+            assert_eq!(token.balance_of(bob), 753);
+            assert_eq!(token.total_supply(), 3000);
+
+            // Mints round down and refuse amounts worth no share
+            assert_eq!(token.mint(2), Err(Error::ZeroAmount));
+            assert!(token.mint(10).is_ok());
+            assert_eq!(token.shares_of(accounts.alice), 752);
+            assert_eq!(token.total_supply(), 3009);
+            let events = test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(decode_transfer(&events[events.len() - 2]).value, 9);
+
+            // Balances never add up to more than the supply
+            let held = token.balance_of(accounts.alice) + token.balance_of(bob);
+            assert!(held <= token.total_supply());
+        }
 
-            test::set_caller(bob);
-            let result = token.blacklist_address(charlie);
-            assert_eq!(result, Err(Error::Unauthorized));
+        #[ink::test]
+        fn rebase_rejects_bad_calls() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new_capped(1000, 1500);
+            let before = token.shares_per_token();
+
+            assert_eq!(token.rebase(0), Err(Error::InvalidRebase));
+            assert_eq!(token.rebase(1501), Err(Error::CapExceeded));
+
+            test::set_caller(get_bob());
+            assert_eq!(token.rebase(1200), Err(Error::Unauthorized));
+
+            assert_eq!(token.shares_per_token(), before);
+            assert_eq!(token.total_supply(), 1000);
         }
 
         #[ink::test]
-        fn mint_works() {
+        fn set_cap_works() {
             let accounts = get_default_accounts();
             test::set_caller(accounts.alice);
 
             let mut token = Token::new(1000);
 
-            assert_eq!(token.total_supply(), 1000);
-            assert_eq!(token.balance_of(accounts.alice), 1000);
+            assert_eq!(token.set_cap(999), Err(Error::CapBelowSupply));
+            assert_eq!(token.max_supply(), 0);
 
-            assert!(token.mint(500).is_ok());
+            assert!(token.set_cap(1500).is_ok());
+            assert_eq!(token.max_supply(), 1500);
 
+            assert!(token.mint(500).is_ok());
+            assert_eq!(token.mint(1), Err(Error::CapExceeded));
             assert_eq!(token.total_supply(), 1500);
-            assert_eq!(token.balance_of(accounts.alice), 1500);
+
+            test::set_caller(get_bob());
+            assert_eq!(token.set_cap(0), Err(Error::Unauthorized));
         }
 
         #[ink::test]
-        fn increase_allowance_works() {
+        fn new_capped_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new_capped(1000, 1200);
+            assert_eq!(token.max_supply(), 1200);
+            assert_eq!(token.total_supply(), 1000);
+
+            // Minting up to the cap
+            assert!(token.mint(150).is_ok());
+            assert!(token.mint_to(get_bob(), 50).is_ok());
+            assert_eq!(token.total_supply(), 1200);
+
+            assert_eq!(token.mint(1), Err(Error::CapExceeded));
+            assert_eq!(token.mint_to(get_bob(), 1), Err(Error::CapExceeded));
+            assert_eq!(token.total_supply(), 1200);
+        }
+
+        #[ink::test]
+        fn default_is_uncapped() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::default();
+            assert_eq!(token.max_supply(), 0);
+            assert!(token.mint(1_000_000_000).is_ok());
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "initial supply exceeds max supply")]
+        fn new_capped_rejects_supply_above_cap() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let _token = Token::new_capped(1000, 999);
+        }
+
+        #[ink::test]
+        fn batch_mint_lenient_works() {
             let accounts = get_default_accounts();
             test::set_caller(accounts.alice);
 
             let mut token = Token::new(1000);
             let bob = get_bob();
+            let charlie = get_charlie();
+            assert!(token.set_cap(1500).is_ok());
 
-            assert!(token.approve(bob, 100).is_ok());
-            assert_eq!(token.allowance(accounts.alice, bob), 100);
-
-            assert!(token.increase_allowance(bob, 50).is_ok());
-            assert_eq!(token.allowance(accounts.alice, bob), 150);
+            let results = token.batch_mint_lenient(vec![
+                (bob, 300),
+                (charlie, 300),
+                (bob, 100),
+            ]);
+
+            // The second entry would exceed the cap, the others still go through
+            assert_eq!(results, vec![Ok(()), Err(Error::CapExceeded), Ok(())]);
+            assert_eq!(token.balance_of(bob), 400);
+            assert_eq!(token.balance_of(charlie), 0);
+            assert_eq!(token.total_supply(), 1400);
+
+            // Each entry gets the same recipient checks as mint_to
+            assert!(token.blacklist_address(charlie).is_ok());
+            let results = token.batch_mint_lenient(vec![
+                (H160::zero(), 10),
+                (charlie, 10),
+                (bob, 0),
+                (bob, 10),
+            ]);
+            assert_eq!(
+                results,
+                vec![Err(Error::ZeroAddress), Err(Error::Blacklisted), Err(Error::ZeroAmount), Ok(())]
+            );
+            assert_eq!(token.balance_of(H160::zero()), 0);
+            assert_eq!(token.balance_of(charlie), 0);
+            assert_eq!(token.total_supply(), 1410);
         }
 
         #[ink::test]
-        fn decrease_allowance_works() {
+        fn received_cap_works() {
             let accounts = get_default_accounts();
             test::set_caller(accounts.alice);
 
             let mut token = Token::new(1000);
             let bob = get_bob();
 
-            assert!(token.approve(bob, 100).is_ok());
-            assert_eq!(token.allowance(accounts.alice, bob), 100);
+            set_timestamp(1000);
+            assert!(token.set_received_cap(bob, 100, 500).is_ok());
+            assert_eq!(token.received_cap_of(bob), Some((100, 500)));
 
-            assert!(token.decrease_allowance(bob, 30).is_ok());
-            assert_eq!(token.allowance(accounts.alice, bob), 70);
+            // Receiving up to the cap
+            assert!(token.transfer(bob, 60).is_ok());
+            assert!(token.transfer(bob, 40).is_ok());
+            assert_eq!(token.received_in_window_of(bob), 100);
+
+            // Going over the cap within the window
+            set_timestamp(1499);
+            assert_eq!(token.transfer(bob, 1), Err(Error::ReceiveCapExceeded));
+            assert_eq!(token.balance_of(bob), 100);
+
+            // A new window starts once the previous one has ended
+            set_timestamp(1500);
+            assert_eq!(token.received_in_window_of(bob), 0);
+            assert!(token.transfer(bob, 100).is_ok());
+            assert_eq!(token.balance_of(bob), 200);
         }
 
         #[ink::test]
-        fn decrease_allowance_insufficient_fails() {
+        fn only_owner_can_set_received_cap() {
             let accounts = get_default_accounts();
             test::set_caller(accounts.alice);
 
             let mut token = Token::new(1000);
             let bob = get_bob();
 
-            assert!(token.approve(bob, 50).is_ok());
+            test::set_caller(bob);
+            assert_eq!(token.set_received_cap(bob, 100, 500), Err(Error::Unauthorized));
+            assert_eq!(token.remove_received_cap(bob), Err(Error::Unauthorized));
+        }
 
-            let result = token.decrease_allowance(bob, 100);
-            assert_eq!(result, Err(Error::InsufficientAllowance));
+        #[ink::test]
+        fn transferable_balance_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+            let bob = get_bob();
+            let charlie = get_charlie();
+
+            assert!(token.transfer(bob, 100).is_ok());
+            assert!(token.transfer(charlie, 200).is_ok());
+            assert_eq!(token.transferable_balance(accounts.alice), 700);
+
+            // Frozen account
+            test::set_caller(bob);
+            assert!(token.freeze_self().is_ok());
+            assert_eq!(token.transferable_balance(bob), 0);
+
+            // Blacklisted account
+            test::set_caller(accounts.alice);
+            assert!(token.blacklist_address(charlie).is_ok());
+            assert_eq!(token.transferable_balance(charlie), 0);
+
+            // Everyone while paused
+            assert!(token.pause().is_ok());
+            assert_eq!(token.transferable_balance(accounts.alice), 0);
+        }
+
+        #[ink::test]
+        fn only_owner_can_set_min_approval() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let mut token = Token::new(1000);
+
+            test::set_caller(get_bob());
+            assert_eq!(token.set_min_approval(100), Err(Error::Unauthorized));
         }
     }
 }
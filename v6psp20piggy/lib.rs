@@ -2,6 +2,8 @@
 
 #[ink::contract]
 mod v6psp20piggybank {
+    use ink::prelude::string::String;
+    use ink::prelude::vec::Vec;
     use ink::storage::Mapping;
     use ink::primitives::{H160, U256};
     use ink::env::call::{build_call, ExecutionInput, Selector};
@@ -41,6 +43,83 @@ mod v6psp20piggybank {
         goal: Balance,
     }
 
+    /// Event emitted when a savings goal changes
+    #[ink(event)]
+    pub struct GoalChanged {
+        #[ink(topic)]
+        owner: H160,
+        old_goal: Balance,
+        new_goal: Balance,
+    }
+
+    /// Event emitted when a lock time is set
+    #[ink(event)]
+    pub struct LockTimeSet {
+        #[ink(topic)]
+        owner: H160,
+        lock_time: u64,
+    }
+
+    /// Event emitted when the owner starts the emergency drain timelock
+    #[ink(event)]
+    pub struct EmergencyDrainInitiated {
+        #[ink(topic)]
+        to: H160,
+        executable_at: u64,
+    }
+
+    /// Event emitted when the emergency drain moves the token balance out
+    #[ink(event)]
+    pub struct EmergencyDrainExecuted {
+        #[ink(topic)]
+        to: H160,
+        amount: Balance,
+    }
+
+    /// Event emitted when a deposit in a token other than the primary token occurs
+    #[ink(event)]
+    pub struct TokenDeposit {
+        #[ink(topic)]
+        owner: H160,
+        #[ink(topic)]
+        token: H160,
+        amount: Balance,
+        total: Balance,
+    }
+
+    /// Event emitted when a withdrawal in a token other than the primary token occurs
+    #[ink(event)]
+    pub struct TokenWithdrawal {
+        #[ink(topic)]
+        owner: H160,
+        #[ink(topic)]
+        token: H160,
+        amount: Balance,
+        remaining: Balance,
+    }
+
+    /// Event emitted when a member deposits into a joint jar
+    #[ink(event)]
+    pub struct JarDeposit {
+        #[ink(topic)]
+        jar_id: u32,
+        #[ink(topic)]
+        member: H160,
+        amount: Balance,
+        total: Balance,
+    }
+
+    /// Event emitted when a member withdraws from a joint jar
+    #[ink(event)]
+    pub struct JarWithdrawal {
+        #[ink(topic)]
+        jar_id: u32,
+        #[ink(topic)]
+        member: H160,
+        amount: Balance,
+        remaining: Balance,
+    }
+
     #[derive(Debug, PartialEq, Eq)]
     #[ink::scale_derive(Encode, Decode, TypeInfo)]
     pub enum Error {
@@ -50,22 +129,188 @@ mod v6psp20piggybank {
         Unauthorized,
         ZeroAmount,
         TokenTransferFailed,
+        InvalidSplit,
+        FeeAlreadyProbed,
+        NoDrainPending,
+        DrainNotReady,
+        OracleCallFailed,
+        InsufficientTokenAllowance,
+        BatchTooLarge,
+        NftCallFailed,
+        ImportClosed,
+        AutoWithdrawDisabled,
+        Overflow,
+        DustNotAllowed,
+        EarlyUnlockDisabled,
+        DepositTransferFailed,
+        WithdrawTransferFailed,
+        ZeroAddress,
+        JarNotFound,
+        NotJarMember,
+        ExceedsMaxWithdrawal,
+        PositionExists,
+        PositionsMinted,
     }
 
     pub type Result<T> = core::result::Result<T, Error>;
 
+    /// Basis points making up 100% of a split
+    pub const BASIS_POINTS: u16 = 10_000;
+
+    /// Base units of the token priced by the oracle (one whole 18-decimal token)
+    pub const PRICE_UNIT: Balance = 1_000_000_000_000_000_000;
+
+    /// Maximum number of entries accepted by batch operations
+    pub const MAX_BATCH_SIZE: usize = 50;
+
+    /// Decimals assumed for tokens that do not expose metadata
+    pub const DEFAULT_DECIMALS: u8 = 18;
+
+    /// Number of recent deposits kept per account to estimate its deposit rate
+    pub const RECENT_DEPOSITS: usize = 5;
+
+    /// Delay (milliseconds) between initiating and executing an emergency drain
+    pub const EMERGENCY_DRAIN_DELAY: u64 = 2 * 24 * 60 * 60 * 1000;
+
+    /// Owner-funded reward pools
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Pool {
+        Interest,
+        Match,
+        Bonus,
+    }
+
+    /// How the goal and lock time conditions combine to release a user's funds
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub enum ReleaseMode {
+        /// The lock time must have passed and the goal (if any) must be reached
+        All,
+        /// Either the lock time has passed or the goal is reached
+        Any,
+    }
+
+    /// A user's complete piggy bank position at the current block
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    pub struct Schedule {
+        pub balance: Balance,
+        pub goal: Balance,
+        pub lock_time: u64,
+        /// Amount that can be withdrawn at the current block timestamp
+        pub withdrawable: Balance,
+    }
+
+    /// Contract-wide aggregates for analytics
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    pub struct Stats {
+        /// Sum of all solo and joint jar balances in the primary token
+        pub total_value_locked: Balance,
+        pub user_count: u32,
+        pub total_deposited_lifetime: Balance,
+        pub total_withdrawn_lifetime: Balance,
+    }
+
     #[ink(storage)]
     pub struct V6psp20piggybank {
         /// Token contract address for CallBuilder (H160 for ink! v6)
         token_address: H160,
         /// Mapping from owner to their balance
         balances: Mapping<H160, Balance>,
+        /// Mapping from owner to the part of their balance that is withdrawable regardless of lock and goal
+        free_balances: Mapping<H160, Balance>,
         /// Mapping from owner to their savings goal
         goals: Mapping<H160, Balance>,
         /// Mapping from owner to their lock time (timestamp)
         lock_times: Mapping<H160, u64>,
+        /// Mapping from owner to the block number their funds are locked until
+        lock_blocks: Mapping<H160, u32>,
+        /// Mapping from owner to the timestamp they aim to reach their goal by
+        deadlines: Mapping<H160, u64>,
         /// Contract owner
         owner: H160,
+        /// Price oracle used to value USD-denominated goals
+        price_oracle: Option<H160>,
+        /// Mapping from owner to their savings goal in USD cents
+        usd_goals: Mapping<H160, Balance>,
+        /// Mapping from owner to their release mode (lock time only when unset)
+        release_modes: Mapping<H160, ReleaseMode>,
+        /// Mapping from owner to the number of goals they have completed
+        goals_completed: Mapping<H160, u32>,
+        /// Mapping from owner to the timestamp they first reached a goal
+        goal_reached_at: Mapping<H160, u64>,
+        /// Accounts whose current goal has already emitted `GoalReached`
+        goal_reached: Mapping<H160, bool>,
+        /// Mapping from owner to the total they have ever deposited
+        lifetime_deposited: Mapping<H160, Balance>,
+        /// Mapping from owner to the total they have ever withdrawn
+        lifetime_withdrawn: Mapping<H160, Balance>,
+        /// Mapping from owner to their largest single deposit
+        largest_deposits: Mapping<H160, Balance>,
+        /// Mapping from owner to their last `RECENT_DEPOSITS` deposits as (timestamp, amount)
+        recent_deposits: Mapping<H160, Vec<(u64, Balance)>>,
+        /// Number of users holding a nonzero balance
+        user_count: u32,
+        /// Sum of all user balances
+        total_balance: Balance,
+        /// Total ever deposited across all users
+        total_deposited: Balance,
+        /// Total ever withdrawn across all users
+        total_withdrawn: Balance,
+        /// Tokens funded by the owner for interest payouts
+        interest_pool: Balance,
+        /// Tokens funded by the owner for deposit matching
+        match_pool: Balance,
+        /// Tokens funded by the owner for bonuses
+        bonus_pool: Balance,
+        /// Pending emergency drain recipient and the timestamp it becomes executable
+        emergency_drain: Option<(H160, u64)>,
+        /// NFT contract minting a position receipt for every deposit
+        position_nft: Option<H160>,
+        /// Id of the next position NFT to mint
+        next_position_id: u128,
+        /// Mapping from owner to the position NFT ids they hold
+        position_ids: Mapping<H160, Vec<u128>>,
+        /// Mapping from (owner, token) to their balance in a token other than the primary token
+        token_balances: Mapping<(H160, H160), Balance>,
+        /// Mapping from (owner, token) to their savings goal in that token
+        token_goals: Mapping<(H160, H160), Balance>,
+        /// Mapping from (owner, token) to the timestamp their savings in that token are locked until
+        token_lock_times: Mapping<(H160, H160), u64>,
+        /// Id of the next joint jar
+        next_jar_id: u32,
+        /// Mapping from joint jar id to the member who created it and manages its membership
+        jar_owners: Mapping<u32, H160>,
+        /// Mapping from joint jar id to the members allowed to deposit and withdraw
+        jar_members: Mapping<u32, Vec<H160>>,
+        /// Mapping from joint jar id to its shared balance
+        jar_balances: Mapping<u32, Balance>,
+        /// Sum of all joint jar balances
+        total_jar_balance: Balance,
+        /// Mapping from a non-primary token to the sum of all balances held in it
+        token_totals: Mapping<H160, Balance>,
+        /// Mapping from owner to the address their balance is sent to once their lock expires
+        auto_withdraws: Mapping<H160, H160>,
+        /// Whether withdrawals are rounded down to whole tokens
+        round_withdrawals: bool,
+        /// Smallest nonzero balance a withdrawal may leave behind (0 disables)
+        min_remaining_balance: Balance,
+        /// Largest amount a single partial withdrawal may take out, if capped
+        max_single_withdrawal: Option<Balance>,
+        /// Fee paid to the owner to clear a lock early (0 disables the buyout)
+        early_unlock_fee: Balance,
+        /// Whether new goals must be above the user's current balance
+        require_goal_above_balance: bool,
+        /// Whether the owner can still seed positions with `import_position`; sealed by the first deposit
+        import_open: bool,
+        /// Cached token (name, symbol, decimals)
+        token_metadata: Option<(String, String, u8)>,
+        /// Whether `probe_token_fee` has already run
+        fee_probed: bool,
+        /// Whether the token takes a fee on transfer, switching deposits to balance-delta accounting
+        token_has_fee: bool,
     }
 
     impl V6psp20piggybank {
@@ -75,345 +320,3214 @@ mod v6psp20piggybank {
             Self {
                 token_address,
                 balances: Mapping::default(),
+                free_balances: Mapping::default(),
                 goals: Mapping::default(),
                 lock_times: Mapping::default(),
+                lock_blocks: Mapping::default(),
+                deadlines: Mapping::default(),
                 owner: Self::env().caller(),
+                price_oracle: None,
+                usd_goals: Mapping::default(),
+                release_modes: Mapping::default(),
+                goals_completed: Mapping::default(),
+                goal_reached_at: Mapping::default(),
+                goal_reached: Mapping::default(),
+                lifetime_deposited: Mapping::default(),
+                lifetime_withdrawn: Mapping::default(),
+                largest_deposits: Mapping::default(),
+                recent_deposits: Mapping::default(),
+                user_count: 0,
+                total_balance: 0,
+                total_deposited: 0,
+                total_withdrawn: 0,
+                interest_pool: 0,
+                match_pool: 0,
+                bonus_pool: 0,
+                emergency_drain: None,
+                position_nft: None,
+                next_position_id: 0,
+                position_ids: Mapping::default(),
+                token_balances: Mapping::default(),
+                token_goals: Mapping::default(),
+                token_lock_times: Mapping::default(),
+                next_jar_id: 0,
+                jar_owners: Mapping::default(),
+                jar_members: Mapping::default(),
+                jar_balances: Mapping::default(),
+                total_jar_balance: 0,
+                token_totals: Mapping::default(),
+                auto_withdraws: Mapping::default(),
+                round_withdrawals: false,
+                min_remaining_balance: 0,
+                max_single_withdrawal: None,
+                early_unlock_fee: 0,
+                require_goal_above_balance: false,
+                import_open: true,
+                token_metadata: None,
+                fee_probed: false,
+                token_has_fee: false,
             }
         }
 
-        /// Deposit tokens into the piggy bank (requires prior approval)
+        /// Deposit `amount` of `token` into the piggy bank (requires prior approval)
         #[ink(message)]
-        pub fn deposit(&mut self, amount: Balance) -> Result<()> {
+        pub fn deposit(&mut self, token: H160, amount: Balance) -> Result<()> {
             let caller = self.env().caller();
+            if token == self.token_address {
+                return self.deposit_for(caller, amount);
+            }
 
             if amount == 0 {
                 return Err(Error::ZeroAmount);
             }
 
-            // Convert AccountId to H160 for cross-contract call
-            let contract_h160: H160 = self.convert_account_to_h160(self.env().account_id());
+            self.balance_of_token(caller, token).checked_add(amount).ok_or(Error::Overflow)?;
 
-            // Use CallBuilder to call transfer_from on the token contract
-            build_call::<DefaultEnvironment>()
-                .call(self.token_address)
-                .transferred_value(U256::zero())
-                .exec_input(
-                    ExecutionInput::new(Selector::new(ink::selector_bytes!("transfer_from")))
-                        .push_arg(caller)
-                        .push_arg(contract_h160)
-                        .push_arg(amount)
-                )
-                .returns::<core::result::Result<(), ()>>()
-                .try_invoke()
-                .map_err(|_| Error::TokenTransferFailed)?
-                .map_err(|_| Error::TokenTransferFailed)?;
+            // Other tokens are always measured, since only the primary token is probed for a transfer fee
+            let received = self.pull_tokens_of_measured(token, caller, amount)?;
+            if received == 0 {
+                return Err(Error::ZeroAmount);
+            }
 
-            let current_balance = self.balance_of(caller);
-            let new_balance = current_balance.saturating_add(amount);
-            self.balances.insert(caller, &new_balance);
+            self.credit_token(caller, token, received);
+            Ok(())
+        }
 
-            self.env().emit_event(Deposit {
-                owner: caller,
-                amount,
-                total: new_balance,
-            });
+        /// Deposit tokens from the caller into `beneficiary`'s piggy bank (requires prior approval)
+        #[ink(message)]
+        pub fn deposit_for(&mut self, beneficiary: H160, amount: Balance) -> Result<()> {
+            let caller = self.env().caller();
 
-            // Check if goal is reached
-            if let Some(goal) = self.goals.get(caller) {
-                if new_balance >= goal {
-                    self.env().emit_event(GoalReached {
-                        owner: caller,
-                        goal,
-                    });
-                }
+            if amount == 0 {
+                return Err(Error::ZeroAmount);
+            }
+
+            if beneficiary == H160::zero() {
+                return Err(Error::ZeroAddress);
             }
 
+            // Fee-on-transfer tokens only ever deliver less than `amount`, so this covers both paths
+            self.balance_of(beneficiary).checked_add(amount).ok_or(Error::Overflow)?;
+
+            let received = if self.token_has_fee {
+                self.pull_tokens_measured(caller, amount)?
+            } else {
+                self.pull_tokens(caller, amount)?;
+                amount
+            };
+
+            self.credit(beneficiary, received);
+            self.mint_position(beneficiary)?;
+
             Ok(())
         }
 
-        /// Set a savings goal
+        /// Deposit `locked_amount + free_amount`; only the locked part is subject to the lock and goal rules
         #[ink(message)]
-        pub fn set_goal(&mut self, goal: Balance) -> Result<()> {
+        pub fn deposit_split(&mut self, locked_amount: Balance, free_amount: Balance) -> Result<()> {
             let caller = self.env().caller();
-            self.goals.insert(caller, &goal);
+            let total = locked_amount.checked_add(free_amount).ok_or(Error::Overflow)?;
+
+            let balance_before = self.balance_of(caller);
+            self.deposit_for(caller, total)?;
+
+            // A fee-on-transfer token may deliver less than `total`; the free part never exceeds what arrived
+            let received = self.balance_of(caller).saturating_sub(balance_before);
+            self.add_free_balance(caller, free_amount.min(received));
+
             Ok(())
         }
 
-        /// Set a lock time (timestamp in milliseconds) - funds cannot be withdrawn until this time
+        /// Deposit tokens and set or extend the lock time in one call. The lock is never shortened.
         #[ink(message)]
-        pub fn set_lock_time(&mut self, lock_time: u64) -> Result<()> {
+        pub fn deposit_with_lock(&mut self, amount: Balance, lock_time: u64) -> Result<()> {
             let caller = self.env().caller();
-            self.lock_times.insert(caller, &lock_time);
+            self.deposit_for(caller, amount)?;
+            self.extend_lock(caller, lock_time);
             Ok(())
         }
 
-        /// Withdraw a specific amount
+        /// One-time probe that deposits `amount` and compares it with what actually arrived
+        /// to detect a fee-on-transfer token (only owner)
         #[ink(message)]
-        pub fn withdraw(&mut self, amount: Balance) -> Result<()> {
+        pub fn probe_token_fee(&mut self, amount: Balance) -> Result<()> {
             let caller = self.env().caller();
-            let current_balance = self.balance_of(caller);
-
-            if amount == 0 {
-                return Err(Error::ZeroAmount);
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
             }
 
-            if current_balance < amount {
-                return Err(Error::InsufficientBalance);
+            if self.fee_probed {
+                return Err(Error::FeeAlreadyProbed);
             }
 
-            // Check lock time
-            if let Some(lock_time) = self.lock_times.get(caller) {
-                if self.env().block_timestamp() < lock_time {
-                    return Err(Error::WithdrawalTooEarly);
-                }
+            if amount == 0 {
+                return Err(Error::ZeroAmount);
             }
 
-            let new_balance = current_balance.saturating_sub(amount);
-            self.balances.insert(caller, &new_balance);
-
-            // Use CallBuilder to call transfer on the token contract
-            build_call::<DefaultEnvironment>()
-                .call(self.token_address)
-                .transferred_value(U256::zero())
-                .exec_input(
-                    ExecutionInput::new(Selector::new(ink::selector_bytes!("transfer")))
-                        .push_arg(caller)
-                        .push_arg(amount)
-                )
-                .returns::<core::result::Result<(), ()>>()
-                .try_invoke()
-                .map_err(|_| Error::TokenTransferFailed)?
-                .map_err(|_| Error::TokenTransferFailed)?;
+            let received = self.pull_tokens_measured(caller, amount)?;
+            self.fee_probed = true;
+            self.token_has_fee = received < amount;
 
-            self.env().emit_event(Withdrawal {
-                owner: caller,
-                amount,
-                remaining: new_balance,
-            });
+            self.credit(caller, received);
 
             Ok(())
         }
 
-        /// Break the piggy bank - withdraw all funds
+        /// Returns whether the token was detected to take a fee on transfer
+        #[ink(message)]
+        pub fn token_has_fee(&self) -> bool {
+            self.token_has_fee
+        }
+
+        /// Set a savings goal
         #[ink(message)]
-        pub fn break_piggy_bank(&mut self) -> Result<()> {
+        pub fn set_goal(&mut self, goal: Balance) -> Result<()> {
             let caller = self.env().caller();
-            let balance = self.balance_of(caller);
 
-            if balance == 0 {
-                return Err(Error::InsufficientBalance);
+            if self.require_goal_above_balance && goal <= self.balance_of(caller) {
+                return Err(Error::GoalNotReached);
             }
 
-            // Check lock time
-            if let Some(lock_time) = self.lock_times.get(caller) {
-                if self.env().block_timestamp() < lock_time {
-                    return Err(Error::WithdrawalTooEarly);
-                }
-            }
+            // A first goal, a lower one, or one already covered by the balance could release funds in Any mode
+            let loosens = match self.goals.get(caller) {
+                None => true,
+                Some(current) => goal < current || goal <= self.balance_of(caller),
+            };
+            self.ensure_goal_change_allowed(caller, loosens)?;
 
-            self.balances.remove(caller);
-            self.goals.remove(caller);
-            self.lock_times.remove(caller);
+            self.update_goal(caller, goal);
+            Ok(())
+        }
 
-            // Use CallBuilder to call transfer on the token contract
-            build_call::<DefaultEnvironment>()
-                .call(self.token_address)
-                .transferred_value(U256::zero())
-                .exec_input(
-                    ExecutionInput::new(Selector::new(ink::selector_bytes!("transfer")))
-                        .push_arg(caller)
-                        .push_arg(balance)
-                )
-                .returns::<core::result::Result<(), ()>>()
-                .try_invoke()
-                .map_err(|_| Error::TokenTransferFailed)?
-                .map_err(|_| Error::TokenTransferFailed)?;
+        /// Set a savings goal in USD cents, valued through the price oracle (0 clears it)
+        #[ink(message)]
+        pub fn set_goal_usd(&mut self, usd_cents: Balance) -> Result<()> {
+            let caller = self.env().caller();
 
-            self.env().emit_event(PiggyBankBroken {
-                owner: caller,
-                amount: balance,
-            });
+            // Only raising an existing USD goal can never make it easier to reach
+            let raises = matches!(self.usd_goals.get(caller), Some(current) if usd_cents >= current);
+            self.ensure_goal_change_allowed(caller, !raises)?;
 
+            if usd_cents == 0 {
+                self.usd_goals.remove(caller);
+            } else {
+                self.usd_goals.insert(caller, &usd_cents);
+            }
             Ok(())
         }
 
-        /// Withdraw if goal is reached
+        /// Set the NFT contract that mints a position receipt per deposit. Fixed once the first
+        /// receipt is minted, since existing ids can only be burned on their own contract (only owner)
         #[ink(message)]
-        pub fn withdraw_if_goal_reached(&mut self, amount: Balance) -> Result<()> {
+        pub fn set_position_nft(&mut self, nft: H160) -> Result<()> {
             let caller = self.env().caller();
-            let current_balance = self.balance_of(caller);
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
+            }
 
-            if let Some(goal) = self.goals.get(caller) {
-                if current_balance < goal {
-                    return Err(Error::GoalNotReached);
-                }
+            if self.next_position_id > 0 {
+                return Err(Error::PositionsMinted);
             }
 
-            self.withdraw(amount)
+            self.position_nft = Some(nft);
+            Ok(())
         }
 
-        /// Returns the balance of the given account
+        /// Enable or disable rounding withdrawals down to whole tokens (only owner)
         #[ink(message)]
-        pub fn balance_of(&self, owner: H160) -> Balance {
-            self.balances.get(owner).unwrap_or(0)
+        pub fn set_round_withdrawals(&mut self, enabled: bool) -> Result<()> {
+            let caller = self.env().caller();
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
+            }
+
+            self.round_withdrawals = enabled;
+            Ok(())
         }
 
-        /// Returns the savings goal of the given account
+        /// Set the smallest nonzero balance a withdrawal may leave, 0 to disable (only owner)
         #[ink(message)]
-        pub fn goal_of(&self, owner: H160) -> Balance {
-            self.goals.get(owner).unwrap_or(0)
+        pub fn set_min_remaining_balance(&mut self, amount: Balance) -> Result<()> {
+            let caller = self.env().caller();
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
+            }
+
+            self.min_remaining_balance = amount;
+            Ok(())
         }
 
-        /// Returns the lock time of the given account
+        /// Cap the amount a single `withdraw`, `withdraw_to` or `withdraw_split` may take out; `break_piggy_bank`
+        /// and auto-withdraw stay uncapped so a full exit is always possible (only owner)
         #[ink(message)]
-        pub fn lock_time_of(&self, owner: H160) -> u64 {
-            self.lock_times.get(owner).unwrap_or(0)
+        pub fn set_max_single_withdrawal(&mut self, max: Option<Balance>) -> Result<()> {
+            let caller = self.env().caller();
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
+            }
+
+            self.max_single_withdrawal = max;
+            Ok(())
         }
 
-        /// Returns whether the goal is reached for an account
+        /// Set the fee for buying an early unlock, 0 to disable the buyout (only owner)
         #[ink(message)]
-        pub fn is_goal_reached(&self, owner: H160) -> bool {
-            let balance = self.balance_of(owner);
-            if let Some(goal) = self.goals.get(owner) {
-                balance >= goal
-            } else {
-                false
+        pub fn set_early_unlock_fee(&mut self, fee: Balance) -> Result<()> {
+            let caller = self.env().caller();
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
             }
+
+            self.early_unlock_fee = fee;
+            Ok(())
         }
 
-        /// Returns the contract owner
+        /// Require new goals to be above the user's current balance (only owner)
         #[ink(message)]
-        pub fn owner(&self) -> H160 {
-            self.owner
+        pub fn set_require_goal_above_balance(&mut self, enabled: bool) -> Result<()> {
+            let caller = self.env().caller();
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
+            }
+
+            self.require_goal_above_balance = enabled;
+            Ok(())
         }
 
-        /// Returns the token contract address
+        /// Set the price oracle used for USD goals (only owner)
         #[ink(message)]
-        pub fn token_address(&self) -> H160 {
-            self.token_address
+        pub fn set_price_oracle(&mut self, oracle: H160) -> Result<()> {
+            let caller = self.env().caller();
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
+            }
+
+            self.price_oracle = Some(oracle);
+            Ok(())
         }
 
-        /// Get token balance of this contract in the PSP20 token
+        /// Set a lock time (timestamp in milliseconds) - funds cannot be withdrawn until this time.
+        /// An active lock is never shortened; `buy_early_unlock` is the only early exit.
         #[ink(message)]
-        pub fn token_balance(&self) -> Balance {
-            let contract_h160 = self.convert_account_to_h160(self.env().account_id());
-
-            // Use CallBuilder to call balance_of on the token contract
-            build_call::<DefaultEnvironment>()
-                .call(self.token_address)
-                .transferred_value(U256::zero())
-                .exec_input(
-                    ExecutionInput::new(Selector::new(ink::selector_bytes!("balance_of")))
-                        .push_arg(contract_h160)
-                )
-                .returns::<Balance>()
-                .try_invoke()
-                .unwrap_or(Ok(0))
-                .unwrap_or(0)
+        pub fn set_lock_time(&mut self, lock_time: u64) -> Result<()> {
+            let caller = self.env().caller();
+            self.extend_lock(caller, lock_time);
+            Ok(())
         }
 
-        /// Helper function to convert AccountId to H160
-        fn convert_account_to_h160(&self, account: AccountId) -> H160 {
-            Self::convert_account_id_to_h160(account)
+        /// Set the timestamp the caller aims to reach their goal by, 0 to clear it
+        #[ink(message)]
+        pub fn set_deadline(&mut self, timestamp: u64) -> Result<()> {
+            let caller = self.env().caller();
+            if timestamp == 0 {
+                self.deadlines.remove(caller);
+            } else {
+                self.deadlines.insert(caller, &timestamp);
+            }
+            Ok(())
         }
 
-        /// Static helper function to convert AccountId to H160
-        fn convert_account_id_to_h160(account: AccountId) -> H160 {
-            let account_bytes = <AccountId as AsRef<[u8]>>::as_ref(&account);
-            let mut h160_bytes = [0u8; 20];
-            h160_bytes.copy_from_slice(&account_bytes[..20]);
-            H160::from(h160_bytes)
+        /// Lock funds until the given block number; applies on top of any timestamp lock and never shortens it
+        #[ink(message)]
+        pub fn set_lock_block(&mut self, block: u32) -> Result<()> {
+            let caller = self.env().caller();
+            let block = block.max(self.lock_block_of(caller));
+            self.lock_blocks.insert(caller, &block);
+            Ok(())
         }
-    }
 
-    #[cfg(test)]
-    mod tests {
-        use super::*;
-        use ink::env::test;
+        /// Choose how the goal and lock time combine to release funds; `Any` can only be chosen once unlocked
+        #[ink(message)]
+        pub fn set_release_mode(&mut self, mode: ReleaseMode) -> Result<()> {
+            let caller = self.env().caller();
+
+            // Switching to Any would let a reached goal release funds that are still locked
+            if mode == ReleaseMode::Any {
+                self.ensure_unlocked(caller)?;
+            }
+
+            self.release_modes.insert(caller, &mode);
+            Ok(())
+        }
+
+        /// Set goal and lock time for many users at once (only owner). Locks are never shortened.
+        #[ink(message)]
+        pub fn configure_many(&mut self, entries: Vec<(H160, Balance, u64)>) -> Result<()> {
+            let caller = self.env().caller();
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
+            }
+
+            if entries.len() > MAX_BATCH_SIZE {
+                return Err(Error::BatchTooLarge);
+            }
+
+            for (user, goal, lock_time) in entries {
+                self.update_goal(user, goal);
+                self.extend_lock(user, lock_time);
+            }
+
+            Ok(())
+        }
+
+        /// Seed a new position from another contract's exported data, pulling `balance` from the caller to back it
+        /// (only owner, until `close_import` or the first deposit)
+        #[ink(message)]
+        pub fn import_position(&mut self, owner: H160, balance: Balance, goal: Balance, lock_time: u64) -> Result<()> {
+            let caller = self.env().caller();
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
+            }
+
+            if !self.import_open {
+                return Err(Error::ImportClosed);
+            }
+
+            if balance == 0 {
+                return Err(Error::ZeroAmount);
+            }
+
+            // Imports seed new positions only; they never overwrite a real balance
+            if self.balance_of(owner) > 0 {
+                return Err(Error::PositionExists);
+            }
+
+            // Every imported balance is backed by tokens the owner sends in
+            let received = if self.token_has_fee {
+                self.pull_tokens_measured(caller, balance)?
+            } else {
+                self.pull_tokens(caller, balance)?;
+                balance
+            };
+
+            self.import_into(owner, received, goal, lock_time);
+            Ok(())
+        }
+
+        /// Permanently disable `import_position` (only owner)
+        #[ink(message)]
+        pub fn close_import(&mut self) -> Result<()> {
+            let caller = self.env().caller();
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
+            }
+
+            self.import_open = false;
+            Ok(())
+        }
+
+        /// Returns whether positions can still be imported
+        #[ink(message)]
+        pub fn import_open(&self) -> bool {
+            self.import_open
+        }
+
+        /// Withdraw a specific amount of `token`
+        #[ink(message)]
+        pub fn withdraw(&mut self, token: H160, amount: Balance) -> Result<()> {
+            let caller = self.env().caller();
+            if token == self.token_address {
+                return self.withdraw_to(caller, amount);
+            }
+
+            if amount == 0 {
+                return Err(Error::ZeroAmount);
+            }
+
+            let remaining = self.debit_token(caller, token, amount)?;
+
+            self.send_tokens(token, caller, amount)?;
+
+            self.env().emit_event(TokenWithdrawal {
+                owner: caller,
+                token,
+                amount,
+                remaining,
+            });
+
+            Ok(())
+        }
+
+        /// Withdraw a specific amount of the caller's savings, sending the tokens to `to`
+        #[ink(message)]
+        pub fn withdraw_to(&mut self, to: H160, amount: Balance) -> Result<()> {
+            let caller = self.env().caller();
+
+            if to == H160::zero() {
+                return Err(Error::ZeroAddress);
+            }
+
+            // In rounding mode only whole tokens leave; the dust stays in the piggy bank
+            let amount = if self.round_withdrawals {
+                let (_, _, decimals) = self.token_metadata();
+                Self::round_to_whole(amount, decimals)
+            } else {
+                amount
+            };
+
+            if amount == 0 {
+                return Err(Error::ZeroAmount);
+            }
+
+            self.ensure_within_max_withdrawal(amount)?;
+
+            let new_balance = self.debit(caller, amount)?;
+            self.burn_positions_if_empty(caller)?;
+
+            self.transfer_tokens(to, amount)?;
+
+            self.env().emit_event(Withdrawal {
+                owner: caller,
+                amount,
+                remaining: new_balance,
+            });
+
+            Ok(())
+        }
+
+        /// Withdraw `total` and split it between recipients by basis points (must sum to 10000)
+        #[ink(message)]
+        pub fn withdraw_split(&mut self, total: Balance, recipients: Vec<(H160, u16)>) -> Result<()> {
+            let caller = self.env().caller();
+            let current_balance = self.balance_of(caller);
+
+            if total == 0 {
+                return Err(Error::ZeroAmount);
+            }
+
+            self.ensure_within_max_withdrawal(total)?;
+
+            if current_balance < total {
+                return Err(Error::InsufficientBalance);
+            }
+
+            self.ensure_can_withdraw(caller)?;
+            let shares = Self::split_amounts(total, &recipients)?;
+
+            let new_balance = current_balance.saturating_sub(total);
+            self.ensure_no_dust(new_balance)?;
+
+            self.set_balance(caller, new_balance);
+            self.record_withdrawal(caller, total);
+            self.burn_positions_if_empty(caller)?;
+
+            for (recipient, share) in shares {
+                if share > 0 {
+                    self.transfer_tokens(recipient, share)?;
+                }
+            }
+
+            self.env().emit_event(Withdrawal {
+                owner: caller,
+                amount: total,
+                remaining: new_balance,
+            });
+
+            Ok(())
+        }
+
+        /// Break the piggy bank - withdraw all funds saved in `token` and clear its goal and lock.
+        /// Deliberately not subject to `max_single_withdrawal`, so a full exit is always possible once the lock has passed.
+        #[ink(message)]
+        pub fn break_piggy_bank(&mut self, token: H160) -> Result<()> {
+            let caller = self.env().caller();
+            if token == self.token_address {
+                return self.break_primary(caller);
+            }
+
+            let balance = self.balance_of_token(caller, token);
+
+            if balance == 0 {
+                return Err(Error::InsufficientBalance);
+            }
+
+            self.debit_token(caller, token, balance)?;
+            self.token_goals.remove((caller, token));
+            self.token_lock_times.remove((caller, token));
+
+            self.send_tokens(token, caller, balance)?;
+
+            self.env().emit_event(TokenWithdrawal {
+                owner: caller,
+                token,
+                amount: balance,
+                remaining: 0,
+            });
+
+            Ok(())
+        }
+
+        /// Enable or disable sending the caller's full balance to `to` once their lock expires
+        #[ink(message)]
+        pub fn set_auto_withdraw_on_expiry(&mut self, to: H160, enabled: bool) -> Result<()> {
+            let caller = self.env().caller();
+            if enabled {
+                self.auto_withdraws.insert(caller, &to);
+            } else {
+                self.auto_withdraws.remove(caller);
+            }
+            Ok(())
+        }
+
+        /// Send the owner's full balance to their auto-withdraw address once their lock has expired
+        /// and their release mode allows a withdrawal (anyone can call)
+        #[ink(message)]
+        pub fn trigger_auto_withdraw(&mut self, owner: H160) -> Result<()> {
+            let (to, amount) = self.debit_auto_withdraw(owner)?;
+
+            self.transfer_tokens(to, amount)?;
+
+            self.env().emit_event(Withdrawal {
+                owner,
+                amount,
+                remaining: 0,
+            });
+
+            Ok(())
+        }
+
+        /// Returns the owner's auto-withdraw address, if enabled
+        #[ink(message)]
+        pub fn auto_withdraw_of(&self, owner: H160) -> Option<H160> {
+            self.auto_withdraws.get(owner)
+        }
+
+        /// Set the caller's savings goal in `token`; withdrawals of that token wait until it is reached
+        #[ink(message)]
+        pub fn set_token_goal(&mut self, token: H160, goal: Balance) -> Result<()> {
+            if token == self.token_address {
+                return self.set_goal(goal);
+            }
+
+            let caller = self.env().caller();
+            self.token_goals.insert((caller, token), &goal);
+            Ok(())
+        }
+
+        /// Lock the caller's savings in `token` until the given timestamp
+        #[ink(message)]
+        pub fn set_token_lock_time(&mut self, token: H160, lock_time: u64) -> Result<()> {
+            if token == self.token_address {
+                return self.set_lock_time(lock_time);
+            }
+
+            let caller = self.env().caller();
+            let lock_time = lock_time.max(self.lock_time_of_token(caller, token));
+            self.token_lock_times.insert((caller, token), &lock_time);
+            Ok(())
+        }
+
+        /// Returns the owner's balance in `token`
+        #[ink(message)]
+        pub fn balance_of_token(&self, owner: H160, token: H160) -> Balance {
+            if token == self.token_address {
+                return self.balance_of(owner);
+            }
+            self.token_balances.get((owner, token)).unwrap_or(0)
+        }
+
+        /// Returns the sum of all balances held in a non-primary `token`
+        #[ink(message)]
+        pub fn token_value_locked(&self, token: H160) -> Balance {
+            self.token_totals.get(token).unwrap_or(0)
+        }
+
+        /// Returns the owner's savings goal in `token`
+        #[ink(message)]
+        pub fn goal_of_token(&self, owner: H160, token: H160) -> Balance {
+            if token == self.token_address {
+                return self.goal_of(owner);
+            }
+            self.token_goals.get((owner, token)).unwrap_or(0)
+        }
+
+        /// Returns the timestamp the owner's savings in `token` are locked until
+        #[ink(message)]
+        pub fn lock_time_of_token(&self, owner: H160, token: H160) -> u64 {
+            if token == self.token_address {
+                return self.lock_time_of(owner);
+            }
+            self.token_lock_times.get((owner, token)).unwrap_or(0)
+        }
+
+        /// Create a joint jar shared with `members`; the caller becomes its owner and first member
+        #[ink(message)]
+        pub fn create_joint_jar(&mut self, members: Vec<H160>) -> Result<u32> {
+            let caller = self.env().caller();
+            if members.len() >= MAX_BATCH_SIZE {
+                return Err(Error::BatchTooLarge);
+            }
+
+            let mut jar_members = Vec::with_capacity(members.len().saturating_add(1));
+            jar_members.push(caller);
+            for member in members {
+                if !jar_members.contains(&member) {
+                    jar_members.push(member);
+                }
+            }
+
+            let jar_id = self.next_jar_id;
+            self.next_jar_id = jar_id.checked_add(1).ok_or(Error::Overflow)?;
+            self.jar_owners.insert(jar_id, &caller);
+            self.jar_members.insert(jar_id, &jar_members);
+            Ok(jar_id)
+        }
+
+        /// Add a member to a joint jar (only jar owner)
+        #[ink(message)]
+        pub fn add_jar_member(&mut self, jar_id: u32, member: H160) -> Result<()> {
+            self.ensure_jar_owner(jar_id, self.env().caller())?;
+
+            let mut members = self.jar_members(jar_id);
+            if members.contains(&member) {
+                return Ok(());
+            }
+            if members.len() >= MAX_BATCH_SIZE {
+                return Err(Error::BatchTooLarge);
+            }
+
+            members.push(member);
+            self.jar_members.insert(jar_id, &members);
+            Ok(())
+        }
+
+        /// Remove a member from a joint jar; the jar owner cannot be removed (only jar owner)
+        #[ink(message)]
+        pub fn remove_jar_member(&mut self, jar_id: u32, member: H160) -> Result<()> {
+            let caller = self.env().caller();
+            self.ensure_jar_owner(jar_id, caller)?;
+
+            if member == caller {
+                return Err(Error::Unauthorized);
+            }
+
+            let mut members = self.jar_members(jar_id);
+            members.retain(|m| *m != member);
+            self.jar_members.insert(jar_id, &members);
+            Ok(())
+        }
+
+        /// Deposit tokens into a joint jar (only jar members, requires prior approval)
+        #[ink(message)]
+        pub fn deposit_to_jar(&mut self, jar_id: u32, amount: Balance) -> Result<()> {
+            let caller = self.env().caller();
+
+            if amount == 0 {
+                return Err(Error::ZeroAmount);
+            }
+
+            self.ensure_jar_member(jar_id, caller)?;
+            self.jar_balance(jar_id).checked_add(amount).ok_or(Error::Overflow)?;
+
+            let received = if self.token_has_fee {
+                self.pull_tokens_measured(caller, amount)?
+            } else {
+                self.pull_tokens(caller, amount)?;
+                amount
+            };
+
+            self.credit_jar(jar_id, caller, received);
+            Ok(())
+        }
+
+        /// Withdraw tokens from a joint jar to the caller (only jar members)
+        #[ink(message)]
+        pub fn withdraw_from_jar(&mut self, jar_id: u32, amount: Balance) -> Result<()> {
+            let caller = self.env().caller();
+
+            if amount == 0 {
+                return Err(Error::ZeroAmount);
+            }
+
+            let remaining = self.debit_jar(jar_id, caller, amount)?;
+
+            self.transfer_tokens(caller, amount)?;
+
+            self.env().emit_event(JarWithdrawal {
+                jar_id,
+                member: caller,
+                amount,
+                remaining,
+            });
+
+            Ok(())
+        }
+
+        /// Returns the members of a joint jar
+        #[ink(message)]
+        pub fn jar_members(&self, jar_id: u32) -> Vec<H160> {
+            self.jar_members.get(jar_id).unwrap_or_default()
+        }
+
+        /// Returns the owner of a joint jar
+        #[ink(message)]
+        pub fn jar_owner(&self, jar_id: u32) -> Option<H160> {
+            self.jar_owners.get(jar_id)
+        }
+
+        /// Returns the shared balance of a joint jar
+        #[ink(message)]
+        pub fn jar_balance(&self, jar_id: u32) -> Balance {
+            self.jar_balances.get(jar_id).unwrap_or(0)
+        }
+
+        /// Pay the early unlock fee to the owner to clear the caller's lock (requires prior approval).
+        /// Nothing is charged when the caller is not locked.
+        #[ink(message)]
+        pub fn buy_early_unlock(&mut self) -> Result<()> {
+            let caller = self.env().caller();
+            let fee = self.early_unlock_fee;
+
+            if fee == 0 {
+                return Err(Error::EarlyUnlockDisabled);
+            }
+
+            if self.ensure_unlocked(caller).is_ok() {
+                return Ok(());
+            }
+
+            self.transfer_tokens_from(self.token_address, caller, self.owner, fee)?;
+            self.clear_lock(caller);
+            Ok(())
+        }
+
+        /// Withdraw if goal is reached
+        #[ink(message)]
+        pub fn withdraw_if_goal_reached(&mut self, amount: Balance) -> Result<()> {
+            let caller = self.env().caller();
+            let current_balance = self.balance_of(caller);
+
+            if let Some(goal) = self.goals.get(caller) {
+                if current_balance < goal {
+                    return Err(Error::GoalNotReached);
+                }
+            }
+
+            self.withdraw_to(caller, amount)
+        }
+
+        /// Pull tokens from the owner into the interest pool (only owner)
+        #[ink(message)]
+        pub fn fund_interest_pool(&mut self, amount: Balance) -> Result<()> {
+            self.fund_pool(Pool::Interest, amount)
+        }
+
+        /// Pull tokens from the owner into the match pool (only owner)
+        #[ink(message)]
+        pub fn fund_match_pool(&mut self, amount: Balance) -> Result<()> {
+            self.fund_pool(Pool::Match, amount)
+        }
+
+        /// Pull tokens from the owner into the bonus pool (only owner)
+        #[ink(message)]
+        pub fn fund_bonus_pool(&mut self, amount: Balance) -> Result<()> {
+            self.fund_pool(Pool::Bonus, amount)
+        }
+
+        /// Returns the (interest, match, bonus) pool balances
+        #[ink(message)]
+        pub fn pool_balances(&self) -> (Balance, Balance, Balance) {
+            (self.interest_pool, self.match_pool, self.bonus_pool)
+        }
+
+        /// Start the timelock for draining the whole token balance to `to` (only owner)
+        #[ink(message)]
+        pub fn initiate_emergency_drain(&mut self, to: H160) -> Result<()> {
+            let caller = self.env().caller();
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
+            }
+
+            let executable_at = self.now_ms().saturating_add(EMERGENCY_DRAIN_DELAY);
+            self.emergency_drain = Some((to, executable_at));
+
+            self.env().emit_event(EmergencyDrainInitiated { to, executable_at });
+
+            Ok(())
+        }
+
+        /// Transfer the whole token balance to the drain recipient once the delay has passed (only owner)
+        #[ink(message)]
+        pub fn execute_emergency_drain(&mut self) -> Result<()> {
+            let caller = self.env().caller();
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
+            }
+
+            let (to, executable_at) = self.emergency_drain.ok_or(Error::NoDrainPending)?;
+            if self.now_ms() < executable_at {
+                return Err(Error::DrainNotReady);
+            }
+
+            self.emergency_drain = None;
+            let amount = self.token_balance()?;
+            self.transfer_tokens(to, amount)?;
+
+            self.env().emit_event(EmergencyDrainExecuted { to, amount });
+
+            Ok(())
+        }
+
+        /// Returns the pending emergency drain recipient and when it becomes executable
+        #[ink(message)]
+        pub fn pending_emergency_drain(&self) -> Option<(H160, u64)> {
+            self.emergency_drain
+        }
+
+        /// Returns the balance of the given account
+        #[ink(message)]
+        pub fn balance_of(&self, owner: H160) -> Balance {
+            self.balances.get(owner).unwrap_or(0)
+        }
+
+        /// Returns the savings goal of the given account
+        #[ink(message)]
+        pub fn goal_of(&self, owner: H160) -> Balance {
+            self.goals.get(owner).unwrap_or(0)
+        }
+
+        /// Returns the USD goal (in cents) of the given account
+        #[ink(message)]
+        pub fn usd_goal_of(&self, owner: H160) -> Balance {
+            self.usd_goals.get(owner).unwrap_or(0)
+        }
+
+        /// Returns the position NFT contract, if set
+        #[ink(message)]
+        pub fn position_nft(&self) -> Option<H160> {
+            self.position_nft
+        }
+
+        /// Returns the position NFT ids held by the given account
+        #[ink(message)]
+        pub fn position_ids_of(&self, owner: H160) -> Vec<u128> {
+            self.position_ids.get(owner).unwrap_or_default()
+        }
+
+        /// Returns whether withdrawals are rounded down to whole tokens
+        #[ink(message)]
+        pub fn round_withdrawals(&self) -> bool {
+            self.round_withdrawals
+        }
+
+        /// Returns the largest amount a single withdrawal may take out, if capped
+        #[ink(message)]
+        pub fn max_single_withdrawal(&self) -> Option<Balance> {
+            self.max_single_withdrawal
+        }
+
+        /// Returns the fee for buying an early unlock (0 when disabled)
+        #[ink(message)]
+        pub fn early_unlock_fee(&self) -> Balance {
+            self.early_unlock_fee
+        }
+
+        /// Returns the smallest nonzero balance a withdrawal may leave
+        #[ink(message)]
+        pub fn min_remaining_balance(&self) -> Balance {
+            self.min_remaining_balance
+        }
+
+        /// Returns whether new goals must be above the user's current balance
+        #[ink(message)]
+        pub fn require_goal_above_balance(&self) -> bool {
+            self.require_goal_above_balance
+        }
+
+        /// Returns the price oracle address, if set
+        #[ink(message)]
+        pub fn price_oracle(&self) -> Option<H160> {
+            self.price_oracle
+        }
+
+        /// Returns the part of the account's balance that can be withdrawn at any time
+        #[ink(message)]
+        pub fn free_balance_of(&self, owner: H160) -> Balance {
+            self.free_balances.get(owner).unwrap_or(0)
+        }
+
+        /// Returns the part of the account's balance that is subject to the lock and goal rules
+        #[ink(message)]
+        pub fn locked_balance_of(&self, owner: H160) -> Balance {
+            self.balance_of(owner).saturating_sub(self.free_balance_of(owner))
+        }
+
+        /// Returns the lock time of the given account
+        #[ink(message)]
+        pub fn lock_time_of(&self, owner: H160) -> u64 {
+            self.lock_times.get(owner).unwrap_or(0)
+        }
+
+        /// Returns the block number the given account is locked until (0 when unset)
+        #[ink(message)]
+        pub fn lock_block_of(&self, owner: H160) -> u32 {
+            self.lock_blocks.get(owner).unwrap_or(0)
+        }
+
+        /// Returns the lock time of the given account as a timestamp in milliseconds
+        #[ink(message)]
+        pub fn lock_time_ms(&self, owner: H160) -> u64 {
+            self.lock_time_of(owner)
+        }
+
+        /// Returns the lock time of the given account as a timestamp in whole seconds, rounded up
+        /// so the account is never reported as unlocked early
+        #[ink(message)]
+        pub fn lock_time_secs(&self, owner: H160) -> u64 {
+            self.lock_time_ms(owner).div_ceil(1000)
+        }
+
+        /// Returns how many goals the given account has completed
+        #[ink(message)]
+        pub fn goals_completed_of(&self, owner: H160) -> u32 {
+            self.goals_completed.get(owner).unwrap_or(0)
+        }
+
+        /// Returns the owner's lifetime (deposited, withdrawn) totals
+        #[ink(message)]
+        pub fn lifetime_stats(&self, owner: H160) -> (Balance, Balance) {
+            (
+                self.lifetime_deposited.get(owner).unwrap_or(0),
+                self.lifetime_withdrawn.get(owner).unwrap_or(0),
+            )
+        }
+
+        /// Returns the timestamp the owner aims to reach their goal by, if set
+        #[ink(message)]
+        pub fn deadline_of(&self, owner: H160) -> Option<u64> {
+            self.deadlines.get(owner)
+        }
+
+        /// Returns progress toward the owner's goal in basis points, capped at 10000 (0 without a goal)
+        #[ink(message)]
+        pub fn goal_progress_bps(&self, owner: H160) -> u16 {
+            let goal = self.goal_of(owner);
+            if goal == 0 {
+                return 0;
+            }
+
+            let progress = U256::from(self.balance_of(owner)) * U256::from(BASIS_POINTS) / U256::from(goal);
+            u16::try_from(progress.min(U256::from(BASIS_POINTS))).unwrap_or(BASIS_POINTS)
+        }
+
+        /// Returns whether the owner's goal deadline has passed
+        #[ink(message)]
+        pub fn is_goal_expired(&self, owner: H160) -> bool {
+            self.deadlines
+                .get(owner)
+                .is_some_and(|deadline| self.now_ms() > deadline)
+        }
+
+        /// Returns when the owner first reached a goal, if ever
+        #[ink(message)]
+        pub fn goal_reached_at(&self, owner: H160) -> Option<u64> {
+            self.goal_reached_at.get(owner)
+        }
+
+        /// Estimates when the owner reaches their goal at their recent deposit rate.
+        /// Returns `None` without a goal, once it is met, or with fewer than two deposits spread over time.
+        #[ink(message)]
+        pub fn projected_completion(&self, owner: H160) -> Option<u64> {
+            let goal = self.goals.get(owner)?;
+            let remaining = goal.checked_sub(self.balance_of(owner)).filter(|r| *r > 0)?;
+
+            // The first recent deposit only marks the start of the window
+            let recent = self.recent_deposits.get(owner)?;
+            let (first, _) = *recent.first()?;
+            let (last, _) = *recent.last()?;
+            let span = last.saturating_sub(first);
+            let saved: Balance = recent.iter().skip(1).map(|(_, amount)| *amount).sum();
+            if span == 0 || saved == 0 {
+                return None;
+            }
+
+            let eta = U256::from(remaining) * U256::from(span) / U256::from(saved);
+            let eta = u64::try_from(eta).unwrap_or(u64::MAX);
+            Some(self.now_ms().saturating_add(eta))
+        }
+
+        /// Returns a goal hint for front-ends: twice the account's largest deposit, or 0 without history
+        #[ink(message)]
+        pub fn suggested_goal(&self, owner: H160) -> Balance {
+            self.largest_deposits.get(owner).unwrap_or(0).saturating_mul(2)
+        }
+
+        /// Returns the release mode of the given account, `None` meaning lock time only
+        #[ink(message)]
+        pub fn release_mode_of(&self, owner: H160) -> Option<ReleaseMode> {
+            self.release_modes.get(owner)
+        }
+
+        /// Returns whether the given account can withdraw at the current block
+        #[ink(message)]
+        pub fn can_withdraw(&self, owner: H160) -> bool {
+            self.ensure_can_withdraw(owner).is_ok()
+        }
+
+        /// Returns the full position of the given account in one call
+        #[ink(message)]
+        pub fn schedule_of(&self, owner: H160) -> Schedule {
+            let balance = self.balance_of(owner);
+            let withdrawable = if self.can_withdraw(owner) {
+                balance
+            } else {
+                self.free_balance_of(owner)
+            };
+
+            Schedule {
+                balance,
+                goal: self.goal_of(owner),
+                lock_time: self.lock_time_of(owner),
+                withdrawable,
+            }
+        }
+
+        /// Returns whether the goal is reached for an account
+        #[ink(message)]
+        pub fn is_goal_reached(&self, owner: H160) -> bool {
+            let balance = self.balance_of(owner);
+            if let (Some(oracle), Some(usd_goal)) = (self.price_oracle, self.usd_goals.get(owner)) {
+                return match self.token_price(oracle) {
+                    Ok(price) => Self::usd_value_of(balance, price) >= usd_goal,
+                    Err(_) => false,
+                };
+            }
+
+            if let Some(goal) = self.goals.get(owner) {
+                balance >= goal
+            } else {
+                false
+            }
+        }
+
+        /// Returns the contract owner
+        #[ink(message)]
+        pub fn owner(&self) -> H160 {
+            self.owner
+        }
+
+        /// Returns the number of users holding a nonzero balance
+        #[ink(message)]
+        pub fn user_count(&self) -> u32 {
+            self.user_count
+        }
+
+        /// Returns contract-wide aggregates in one call
+        #[ink(message)]
+        pub fn stats(&self) -> Stats {
+            Stats {
+                total_value_locked: self.total_balance.saturating_add(self.total_jar_balance),
+                user_count: self.user_count,
+                total_deposited_lifetime: self.total_deposited,
+                total_withdrawn_lifetime: self.total_withdrawn,
+            }
+        }
+
+        /// Returns the token contract address
+        #[ink(message)]
+        pub fn token_address(&self) -> H160 {
+            self.token_address
+        }
+
+        /// Returns the token's (name, symbol, decimals), fetching and caching them once every read succeeds.
+        /// Failed reads report empty strings and 18 decimals and are retried on the next call.
+        #[ink(message)]
+        pub fn token_metadata(&mut self) -> (String, String, u8) {
+            if let Some(metadata) = self.token_metadata.clone() {
+                return metadata;
+            }
+
+            let name = self.query_token_string(ink::selector_bytes!("token_name"));
+            let symbol = self.query_token_string(ink::selector_bytes!("token_symbol"));
+            let decimals = self.query_token_decimals();
+
+            if let (Some(name), Some(symbol), Some(decimals)) = (&name, &symbol, decimals) {
+                self.token_metadata = Some((name.clone(), symbol.clone(), decimals));
+            }
+
+            (
+                name.unwrap_or_default(),
+                symbol.unwrap_or_default(),
+                decimals.unwrap_or(DEFAULT_DECIMALS),
+            )
+        }
+
+        /// Get token balance of this contract in the PSP20 token
+        #[ink(message)]
+        pub fn token_balance(&self) -> Result<Balance> {
+            self.token_balance_of(self.token_address)
+        }
+
+        /// This contract's balance in `token`
+        fn token_balance_of(&self, token: H160) -> Result<Balance> {
+            let contract_h160 = self.contract_address();
+
+            // Use CallBuilder to call balance_of on the token contract
+            build_call::<DefaultEnvironment>()
+                .call(token)
+                .transferred_value(U256::zero())
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink::selector_bytes!("balance_of")))
+                        .push_arg(contract_h160)
+                )
+                .returns::<Balance>()
+                .try_invoke()
+                .map_err(|_| Error::TokenTransferFailed)?
+                .map_err(|_| Error::TokenTransferFailed)
+        }
+
+        /// Pull tokens from the owner and credit them to a reward pool
+        fn fund_pool(&mut self, pool: Pool, amount: Balance) -> Result<()> {
+            let caller = self.env().caller();
+            if caller != self.owner {
+                return Err(Error::Unauthorized);
+            }
+
+            if amount == 0 {
+                return Err(Error::ZeroAmount);
+            }
+
+            let received = if self.token_has_fee {
+                self.pull_tokens_measured(caller, amount)?
+            } else {
+                self.pull_tokens(caller, amount)?;
+                amount
+            };
+
+            self.credit_pool(pool, received);
+            Ok(())
+        }
+
+        /// Add tokens already held by the piggy bank to a reward pool
+        fn credit_pool(&mut self, pool: Pool, amount: Balance) {
+            let balance = match pool {
+                Pool::Interest => &mut self.interest_pool,
+                Pool::Match => &mut self.match_pool,
+                Pool::Bonus => &mut self.bonus_pool,
+            };
+            *balance = balance.saturating_add(amount);
+        }
+
+        /// Stores a new goal and emits GoalChanged
+        fn update_goal(&mut self, owner: H160, goal: Balance) {
+            let old_goal = self.goal_of(owner);
+            self.goals.insert(owner, &goal);
+
+            // A goal above the balance is a fresh target that can be reached again
+            if goal > self.balance_of(owner) {
+                self.goal_reached.remove(owner);
+            }
+
+            self.env().emit_event(GoalChanged {
+                owner,
+                old_goal,
+                new_goal: goal,
+            });
+        }
+
+        /// Moves the owner's lock time to `lock_time` unless that would shorten it
+        fn extend_lock(&mut self, owner: H160, lock_time: u64) {
+            let lock_time = lock_time.max(self.lock_time_of(owner));
+            self.lock_times.insert(owner, &lock_time);
+
+            self.env().emit_event(LockTimeSet { owner, lock_time });
+        }
+
+        /// Current block timestamp in milliseconds, the unit every lock time and delay is stored in.
+        /// The ink! environment reports `block_timestamp` in milliseconds.
+        fn now_ms(&self) -> u64 {
+            self.env().block_timestamp()
+        }
+
+        /// Fails if the owner's lock time or lock block has not passed yet
+        fn ensure_unlocked(&self, owner: H160) -> Result<()> {
+            if let Some(lock_time) = self.lock_times.get(owner) {
+                if self.now_ms() < lock_time {
+                    return Err(Error::WithdrawalTooEarly);
+                }
+            }
+            if let Some(lock_block) = self.lock_blocks.get(owner) {
+                if self.env().block_number() < lock_block {
+                    return Err(Error::WithdrawalTooEarly);
+                }
+            }
+            Ok(())
+        }
+
+        /// Withdraws the owner's whole primary-token balance and clears their goal and locks
+        fn break_primary(&mut self, caller: H160) -> Result<()> {
+            let balance = self.balance_of(caller);
+
+            if balance == 0 {
+                return Err(Error::InsufficientBalance);
+            }
+
+            self.ensure_can_withdraw(caller)?;
+
+            self.set_balance(caller, 0);
+            self.record_withdrawal(caller, balance);
+            self.burn_positions_if_empty(caller)?;
+            self.goals.remove(caller);
+            self.goal_reached.remove(caller);
+            self.deadlines.remove(caller);
+            self.lock_times.remove(caller);
+            self.lock_blocks.remove(caller);
+
+            self.transfer_tokens(caller, balance)?;
+
+            self.env().emit_event(PiggyBankBroken {
+                owner: caller,
+                amount: balance,
+            });
+
+            Ok(())
+        }
+
+        /// Clears the owner's balance for an auto-withdraw, returning the destination and amount
+        fn debit_auto_withdraw(&mut self, owner: H160) -> Result<(H160, Balance)> {
+            let to = self.auto_withdraws.get(owner).ok_or(Error::AutoWithdrawDisabled)?;
+            let balance = self.balance_of(owner);
+
+            if balance == 0 {
+                return Err(Error::InsufficientBalance);
+            }
+
+            // Same release rules as a manual withdrawal, including an unreached goal in All mode
+            self.ensure_can_withdraw(owner)?;
+
+            self.set_balance(owner, 0);
+            self.record_withdrawal(owner, balance);
+            self.burn_positions_if_empty(owner)?;
+
+            Ok((to, balance))
+        }
+
+        /// Removes `amount` from the owner's balance for a withdrawal, returning the new balance.
+        /// The free balance is spent first and only amounts beyond it need the lock and goal to allow it.
+        fn debit(&mut self, owner: H160, amount: Balance) -> Result<Balance> {
+            let current_balance = self.balance_of(owner);
+            if current_balance < amount {
+                return Err(Error::InsufficientBalance);
+            }
+
+            let free_balance = self.free_balance_of(owner);
+            if amount > free_balance {
+                self.ensure_can_withdraw(owner)?;
+            }
+
+            let new_balance = current_balance.saturating_sub(amount);
+            self.ensure_no_dust(new_balance)?;
+
+            self.set_free_balance(owner, free_balance.saturating_sub(amount));
+            self.set_balance(owner, new_balance);
+            self.record_withdrawal(owner, amount);
+            Ok(new_balance)
+        }
+
+        /// Credits a deposit in a non-primary token and emits the token deposit event
+        fn credit_token(&mut self, owner: H160, token: H160, amount: Balance) {
+            let total = self.balance_of_token(owner, token).saturating_add(amount);
+            self.token_balances.insert((owner, token), &total);
+            let token_total = self.token_value_locked(token).saturating_add(amount);
+            self.token_totals.insert(token, &token_total);
+
+            self.env().emit_event(TokenDeposit {
+                owner,
+                token,
+                amount,
+                total,
+            });
+        }
+
+        /// Takes `amount` of a non-primary token from the owner once its lock has passed and its goal,
+        /// if any, is reached, returning what remains
+        fn debit_token(&mut self, owner: H160, token: H160, amount: Balance) -> Result<Balance> {
+            let balance = self.balance_of_token(owner, token);
+            let remaining = balance.checked_sub(amount).ok_or(Error::InsufficientBalance)?;
+
+            if self.now_ms() < self.lock_time_of_token(owner, token) {
+                return Err(Error::WithdrawalTooEarly);
+            }
+
+            // Token goals always combine with the lock, like the primary token's `All` release mode
+            if balance < self.goal_of_token(owner, token) {
+                return Err(Error::GoalNotReached);
+            }
+
+            if remaining == 0 {
+                self.token_balances.remove((owner, token));
+            } else {
+                self.token_balances.insert((owner, token), &remaining);
+            }
+            let token_total = self.token_value_locked(token).saturating_sub(amount);
+            self.token_totals.insert(token, &token_total);
+            Ok(remaining)
+        }
+
+        /// Fails unless the joint jar exists and `account` is its owner
+        fn ensure_jar_owner(&self, jar_id: u32, account: H160) -> Result<()> {
+            match self.jar_owners.get(jar_id) {
+                None => Err(Error::JarNotFound),
+                Some(owner) if owner != account => Err(Error::Unauthorized),
+                Some(_) => Ok(()),
+            }
+        }
+
+        /// Fails unless the joint jar exists and `account` is one of its members
+        fn ensure_jar_member(&self, jar_id: u32, account: H160) -> Result<()> {
+            let members = self.jar_members.get(jar_id).ok_or(Error::JarNotFound)?;
+            if !members.contains(&account) {
+                return Err(Error::NotJarMember);
+            }
+            Ok(())
+        }
+
+        /// Credits a deposit to a joint jar and emits the jar deposit event
+        fn credit_jar(&mut self, jar_id: u32, member: H160, amount: Balance) {
+            let total = self.jar_balance(jar_id).saturating_add(amount);
+            self.jar_balances.insert(jar_id, &total);
+            self.total_jar_balance = self.total_jar_balance.saturating_add(amount);
+
+            self.env().emit_event(JarDeposit {
+                jar_id,
+                member,
+                amount,
+                total,
+            });
+        }
+
+        /// Takes `amount` out of a joint jar for a member, returning the jar's remaining balance
+        fn debit_jar(&mut self, jar_id: u32, member: H160, amount: Balance) -> Result<Balance> {
+            self.ensure_jar_member(jar_id, member)?;
+
+            let remaining = self
+                .jar_balance(jar_id)
+                .checked_sub(amount)
+                .ok_or(Error::InsufficientBalance)?;
+            self.jar_balances.insert(jar_id, &remaining);
+            self.total_jar_balance = self.total_jar_balance.saturating_sub(amount);
+            Ok(remaining)
+        }
+
+        /// In `Any` mode a goal releases funds early, so goal changes that `loosens` wait until the lock has passed
+        fn ensure_goal_change_allowed(&self, owner: H160, loosens: bool) -> Result<()> {
+            if loosens && self.release_modes.get(owner) == Some(ReleaseMode::Any) {
+                self.ensure_unlocked(owner)?;
+            }
+            Ok(())
+        }
+
+        /// Removes the owner's time and block locks
+        fn clear_lock(&mut self, owner: H160) {
+            self.lock_times.remove(owner);
+            self.lock_blocks.remove(owner);
+
+            self.env().emit_event(LockTimeSet { owner, lock_time: 0 });
+        }
+
+        /// Fails if `amount` is above the single withdrawal cap
+        fn ensure_within_max_withdrawal(&self, amount: Balance) -> Result<()> {
+            match self.max_single_withdrawal {
+                Some(max) if amount > max => Err(Error::ExceedsMaxWithdrawal),
+                _ => Ok(()),
+            }
+        }
+
+        /// Fails if a withdrawal would leave a nonzero balance below min_remaining_balance
+        fn ensure_no_dust(&self, new_balance: Balance) -> Result<()> {
+            if new_balance > 0 && new_balance < self.min_remaining_balance {
+                return Err(Error::DustNotAllowed);
+            }
+            Ok(())
+        }
+
+        /// Adds a withdrawal to the owner's lifetime withdrawn total
+        fn record_withdrawal(&mut self, owner: H160, amount: Balance) {
+            let withdrawn = self.lifetime_withdrawn.get(owner).unwrap_or(0).saturating_add(amount);
+            self.lifetime_withdrawn.insert(owner, &withdrawn);
+            self.total_withdrawn = self.total_withdrawn.saturating_add(amount);
+        }
+
+        /// Moves up to `amount` of the owner's balance into their free balance
+        fn add_free_balance(&mut self, owner: H160, amount: Balance) {
+            let free_balance = self.free_balance_of(owner).saturating_add(amount);
+            self.set_free_balance(owner, free_balance.min(self.balance_of(owner)));
+        }
+
+        /// Writes an owner's free balance, removing the entry at zero
+        fn set_free_balance(&mut self, owner: H160, free_balance: Balance) {
+            if free_balance == 0 {
+                self.free_balances.remove(owner);
+            } else {
+                self.free_balances.insert(owner, &free_balance);
+            }
+        }
+
+        /// Writes an owner's balance, keeping the user count and free balance in sync
+        fn set_balance(&mut self, owner: H160, new_balance: Balance) {
+            let current_balance = self.balance_of(owner);
+            if current_balance == 0 && new_balance > 0 {
+                self.user_count = self.user_count.saturating_add(1);
+            } else if current_balance > 0 && new_balance == 0 {
+                self.user_count = self.user_count.saturating_sub(1);
+            }
+
+            self.total_balance = self
+                .total_balance
+                .saturating_sub(current_balance)
+                .saturating_add(new_balance);
+
+            if new_balance == 0 {
+                self.balances.remove(owner);
+            } else {
+                self.balances.insert(owner, &new_balance);
+            }
+
+            // The free part can never exceed the whole balance
+            if self.free_balance_of(owner) > new_balance {
+                self.set_free_balance(owner, new_balance);
+            }
+        }
+
+        /// Seeds a migrated position once its tokens have arrived
+        fn import_into(&mut self, owner: H160, balance: Balance, goal: Balance, lock_time: u64) {
+            self.set_balance(owner, balance);
+            self.update_goal(owner, goal);
+            self.extend_lock(owner, lock_time);
+        }
+
+        /// Credits a deposit to the owner's balance and emits the deposit events
+        fn credit(&mut self, owner: H160, amount: Balance) {
+            // The migration window ends with the first real deposit
+            self.import_open = false;
+
+            let current_balance = self.balance_of(owner);
+            let new_balance = current_balance.saturating_add(amount);
+            self.set_balance(owner, new_balance);
+
+            let deposited = self.lifetime_deposited.get(owner).unwrap_or(0).saturating_add(amount);
+            self.lifetime_deposited.insert(owner, &deposited);
+            self.total_deposited = self.total_deposited.saturating_add(amount);
+
+            if amount > self.largest_deposits.get(owner).unwrap_or(0) {
+                self.largest_deposits.insert(owner, &amount);
+            }
+
+            let mut recent = self.recent_deposits.get(owner).unwrap_or_default();
+            if recent.len() >= RECENT_DEPOSITS {
+                recent.remove(0);
+            }
+            recent.push((self.now_ms(), amount));
+            self.recent_deposits.insert(owner, &recent);
+
+            self.env().emit_event(Deposit {
+                owner,
+                amount,
+                total: new_balance,
+            });
+
+            // Check if goal is reached
+            if let Some(goal) = self.goals.get(owner) {
+                // GoalReached fires and the goal counts as completed once per goal, not on every crossing
+                if new_balance >= goal && !self.goal_reached.get(owner).unwrap_or(false) {
+                    self.goal_reached.insert(owner, &true);
+                    self.env().emit_event(GoalReached {
+                        owner,
+                        goal,
+                    });
+
+                    let completed = self.goals_completed_of(owner).saturating_add(1);
+                    self.goals_completed.insert(owner, &completed);
+
+                    if !self.goal_reached_at.contains(owner) {
+                        let now = self.now_ms();
+                        self.goal_reached_at.insert(owner, &now);
+                    }
+                }
+            }
+        }
+
+        /// Pull tokens from `from` into the piggy bank via the token's `transfer_from`
+        fn pull_tokens(&self, from: H160, amount: Balance) -> Result<()> {
+            self.pull_tokens_of(self.token_address, from, amount)
+        }
+
+        /// Pull `token` from `from` into the piggy bank via that token's `transfer_from`
+        fn pull_tokens_of(&self, token: H160, from: H160, amount: Balance) -> Result<()> {
+            let contract_h160 = self.contract_address();
+            self.transfer_tokens_from(token, from, contract_h160, amount)
+                .map_err(|e| Self::tag_transfer_error(e, Error::DepositTransferFailed))
+        }
+
+        /// Replaces a generic token transfer failure with the error for the leg it happened on
+        fn tag_transfer_error(error: Error, leg: Error) -> Error {
+            if error == Error::TokenTransferFailed {
+                leg
+            } else {
+                error
+            }
+        }
+
+        /// Move `amount` of `token` from `from` to `to` using the allowance `from` granted this contract
+        fn transfer_tokens_from(&self, token: H160, from: H160, to: H160, amount: Balance) -> Result<()> {
+            let contract_h160 = self.contract_address();
+
+            // Fail with a clear error before transfer_from if the approval does not cover the amount
+            if self.token_allowance(token, from, contract_h160)? < amount {
+                return Err(Error::InsufficientTokenAllowance);
+            }
+
+            // Use CallBuilder to call transfer_from on the token contract
+            build_call::<DefaultEnvironment>()
+                .call(token)
+                .transferred_value(U256::zero())
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink::selector_bytes!("transfer_from")))
+                        .push_arg(from)
+                        .push_arg(to)
+                        .push_arg(amount)
+                )
+                .returns::<core::result::Result<(), ()>>()
+                .try_invoke()
+                .map_err(|_| Error::TokenTransferFailed)?
+                .map_err(|_| Error::TokenTransferFailed)?;
+            Ok(())
+        }
+
+        /// Query `token` for the allowance `owner` has granted to `spender`
+        fn token_allowance(&self, token: H160, owner: H160, spender: H160) -> Result<Balance> {
+            build_call::<DefaultEnvironment>()
+                .call(token)
+                .transferred_value(U256::zero())
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink::selector_bytes!("allowance")))
+                        .push_arg(owner)
+                        .push_arg(spender)
+                )
+                .returns::<Balance>()
+                .try_invoke()
+                .map_err(|_| Error::TokenTransferFailed)?
+                .map_err(|_| Error::TokenTransferFailed)
+        }
+
+        /// Query a string getter on the token, returning `None` if the call fails
+        fn query_token_string(&self, selector: [u8; 4]) -> Option<String> {
+            build_call::<DefaultEnvironment>()
+                .call(self.token_address)
+                .transferred_value(U256::zero())
+                .exec_input(ExecutionInput::new(Selector::new(selector)))
+                .returns::<String>()
+                .try_invoke()
+                .ok()?
+                .ok()
+        }
+
+        /// Query the token's decimals, returning `None` if the call fails
+        fn query_token_decimals(&self) -> Option<u8> {
+            build_call::<DefaultEnvironment>()
+                .call(self.token_address)
+                .transferred_value(U256::zero())
+                .exec_input(ExecutionInput::new(Selector::new(ink::selector_bytes!("token_decimals"))))
+                .returns::<u8>()
+                .try_invoke()
+                .ok()?
+                .ok()
+        }
+
+        /// Pull tokens and return how many actually arrived, measured from the token balance delta
+        fn pull_tokens_measured(&self, from: H160, amount: Balance) -> Result<Balance> {
+            self.pull_tokens_of_measured(self.token_address, from, amount)
+        }
+
+        /// Pull `token` and return how many actually arrived, measured from this contract's balance delta
+        fn pull_tokens_of_measured(&self, token: H160, from: H160, amount: Balance) -> Result<Balance> {
+            let before = self.token_balance_of(token)?;
+            self.pull_tokens_of(token, from, amount)?;
+            Ok(self.token_balance_of(token)?.saturating_sub(before))
+        }
+
+        /// Fails unless the owner's release conditions are met under their release mode
+        fn ensure_can_withdraw(&self, owner: H160) -> Result<()> {
+            let unlocked = self.ensure_unlocked(owner);
+            match self.release_modes.get(owner) {
+                None => unlocked,
+                Some(ReleaseMode::All) => {
+                    unlocked?;
+                    let has_goal = self.goals.get(owner).is_some() || self.usd_goals.get(owner).is_some();
+                    if has_goal && !self.is_goal_reached(owner) {
+                        return Err(Error::GoalNotReached);
+                    }
+                    Ok(())
+                }
+                Some(ReleaseMode::Any) => {
+                    if self.is_goal_reached(owner) {
+                        Ok(())
+                    } else {
+                        unlocked
+                    }
+                }
+            }
+        }
+
+        /// Mint a position NFT for a deposit, if a position NFT contract is configured
+        fn mint_position(&mut self, owner: H160) -> Result<()> {
+            let Some(nft) = self.position_nft else {
+                return Ok(());
+            };
+
+            let id = self.next_position_id;
+            build_call::<DefaultEnvironment>()
+                .call(nft)
+                .transferred_value(U256::zero())
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink::selector_bytes!("mint")))
+                        .push_arg(owner)
+                        .push_arg(id)
+                )
+                .returns::<core::result::Result<(), ()>>()
+                .try_invoke()
+                .map_err(|_| Error::NftCallFailed)?
+                .map_err(|_| Error::NftCallFailed)?;
+
+            self.next_position_id = id.saturating_add(1);
+            let mut ids = self.position_ids_of(owner);
+            ids.push(id);
+            self.position_ids.insert(owner, &ids);
+            Ok(())
+        }
+
+        /// Burn all of the owner's position NFTs once their balance is fully withdrawn
+        fn burn_positions_if_empty(&mut self, owner: H160) -> Result<()> {
+            if self.balance_of(owner) > 0 {
+                return Ok(());
+            }
+
+            let ids = self.position_ids_of(owner);
+            if ids.is_empty() {
+                return Ok(());
+            }
+            let nft = self.position_nft.ok_or(Error::NftCallFailed)?;
+
+            for id in ids {
+                build_call::<DefaultEnvironment>()
+                    .call(nft)
+                    .transferred_value(U256::zero())
+                    .exec_input(
+                        ExecutionInput::new(Selector::new(ink::selector_bytes!("burn")))
+                            .push_arg(owner)
+                            .push_arg(id)
+                    )
+                    .returns::<core::result::Result<(), ()>>()
+                    .try_invoke()
+                    .map_err(|_| Error::NftCallFailed)?
+                    .map_err(|_| Error::NftCallFailed)?;
+            }
+
+            self.position_ids.remove(owner);
+            Ok(())
+        }
+
+        /// Query the oracle for the USD cents value of `PRICE_UNIT` base units of the token
+        fn token_price(&self, oracle: H160) -> Result<Balance> {
+            build_call::<DefaultEnvironment>()
+                .call(oracle)
+                .transferred_value(U256::zero())
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink::selector_bytes!("get_price")))
+                        .push_arg(self.token_address)
+                )
+                .returns::<Balance>()
+                .try_invoke()
+                .map_err(|_| Error::OracleCallFailed)?
+                .map_err(|_| Error::OracleCallFailed)
+        }
+
+        /// Rounds `amount` down to a whole number of tokens with the given decimals
+        fn round_to_whole(amount: Balance, decimals: u8) -> Balance {
+            match Balance::from(10u8).checked_pow(u32::from(decimals)) {
+                Some(unit) => amount - amount % unit,
+                None => 0,
+            }
+        }
+
+        /// Values `amount` base units in USD cents at `price` cents per `PRICE_UNIT`
+        fn usd_value_of(amount: Balance, price: Balance) -> Balance {
+            let value = U256::from(amount) * U256::from(price) / U256::from(PRICE_UNIT);
+            Balance::try_from(value).unwrap_or(Balance::MAX)
+        }
+
+        /// Send tokens held by the piggy bank to `to` via the token's `transfer`
+        fn transfer_tokens(&self, to: H160, amount: Balance) -> Result<()> {
+            self.send_tokens(self.token_address, to, amount)
+        }
+
+        /// Send `token` held by the piggy bank to `to` via that token's `transfer`
+        fn send_tokens(&self, token: H160, to: H160, amount: Balance) -> Result<()> {
+            build_call::<DefaultEnvironment>()
+                .call(token)
+                .transferred_value(U256::zero())
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink::selector_bytes!("transfer")))
+                        .push_arg(to)
+                        .push_arg(amount)
+                )
+                .returns::<core::result::Result<(), ()>>()
+                .try_invoke()
+                .map_err(|_| Error::WithdrawTransferFailed)?
+                .map_err(|_| Error::WithdrawTransferFailed)?;
+            Ok(())
+        }
+
+        /// Splits `total` by basis points; the last recipient receives the rounding dust
+        fn split_amounts(total: Balance, recipients: &[(H160, u16)]) -> Result<Vec<(H160, Balance)>> {
+            let bps_sum: u64 = recipients.iter().map(|(_, bps)| u64::from(*bps)).sum();
+            if recipients.is_empty() || bps_sum != u64::from(BASIS_POINTS) {
+                return Err(Error::InvalidSplit);
+            }
+
+            let mut remaining = total;
+            let mut shares = Vec::with_capacity(recipients.len());
+            for (i, (recipient, bps)) in recipients.iter().enumerate() {
+                let share = if i + 1 == recipients.len() {
+                    remaining
+                } else {
+                    Self::bps_of(total, *bps)
+                };
+                remaining = remaining.saturating_sub(share);
+                shares.push((*recipient, share));
+            }
+            Ok(shares)
+        }
+
+        /// Returns `bps` basis points of `amount` without overflowing
+        fn bps_of(amount: Balance, bps: u16) -> Balance {
+            let bps = Balance::from(bps);
+            let scale = Balance::from(BASIS_POINTS);
+            amount / scale * bps + amount % scale * bps / scale
+        }
+
+        /// Returns this contract's own address, the account the token sees it as
+        fn contract_address(&self) -> H160 {
+            self.env().address()
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use ink::env::test;
+
+        fn get_default_accounts() -> test::DefaultAccounts {
+            test::default_accounts()
+        }
+
+        fn get_bob() -> H160 {
+            H160::from([2u8; 20])
+        }
+
+        fn get_charlie() -> H160 {
+            H160::from([3u8; 20])
+        }
+
+        fn set_timestamp(timestamp: u64) {
+            test::set_block_timestamp::<DefaultEnvironment>(timestamp);
+        }
+
+        fn set_block_number(block: u32) {
+            test::set_block_number::<DefaultEnvironment>(block);
+        }
+
+        fn create_mock_token() -> H160 {
+            // Create a mock token contract address for testing (H160 for ink! v6)
+            H160::from([0x01; 20])
+        }
+
+        #[ink::test]
+        fn new_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let piggy_bank = V6psp20piggybank::new(token_address);
+
+            assert_eq!(piggy_bank.balance_of(accounts.alice), 0);
+            assert_eq!(piggy_bank.owner(), accounts.alice);
+        }
+
+        #[ink::test]
+        fn set_goal_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+
+            assert!(piggy_bank.set_goal(1000).is_ok());
+            assert_eq!(piggy_bank.goal_of(accounts.alice), 1000);
+        }
+
+        #[ink::test]
+        fn set_goal_emits_goal_changed() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+
+            piggy_bank.set_goal(1000).unwrap();
+            piggy_bank.set_goal(2500).unwrap();
+
+            let events = test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(events.len(), 2);
+
+            let first = <GoalChanged as ink::scale::Decode>::decode(&mut &events[0].data[..])
+                .expect("invalid GoalChanged event data");
+            assert_eq!(first.owner, accounts.alice);
+            assert_eq!((first.old_goal, first.new_goal), (0, 1000));
+
+            let second = <GoalChanged as ink::scale::Decode>::decode(&mut &events[1].data[..])
+                .expect("invalid GoalChanged event data");
+            assert_eq!((second.old_goal, second.new_goal), (1000, 2500));
+        }
+
+        #[ink::test]
+        fn goal_progress_and_deadline() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+
+            // No goal means no progress
+            piggy_bank.credit(accounts.alice, 100);
+            assert_eq!(piggy_bank.goal_progress_bps(accounts.alice), 0);
+
+            piggy_bank.set_goal(1000).unwrap();
+            piggy_bank.credit(accounts.alice, 540);
+            assert_eq!(piggy_bank.goal_progress_bps(accounts.alice), 6400);
+
+            // A met goal is capped at 100%
+            piggy_bank.credit(accounts.alice, 900);
+            assert_eq!(piggy_bank.goal_progress_bps(accounts.alice), BASIS_POINTS);
+
+            set_timestamp(1000);
+            assert!(!piggy_bank.is_goal_expired(accounts.alice));
+            assert!(piggy_bank.set_deadline(2000).is_ok());
+            assert_eq!(piggy_bank.deadline_of(accounts.alice), Some(2000));
+
+            set_timestamp(2000);
+            assert!(!piggy_bank.is_goal_expired(accounts.alice));
+            set_timestamp(2001);
+            assert!(piggy_bank.is_goal_expired(accounts.alice));
+
+            assert!(piggy_bank.set_deadline(0).is_ok());
+            assert_eq!(piggy_bank.deadline_of(accounts.alice), None);
+            assert!(!piggy_bank.is_goal_expired(accounts.alice));
+        }
+
+        #[ink::test]
+        fn goal_reached_emits_once_per_goal() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+            piggy_bank.set_goal(500).unwrap();
+
+            // Crossing the goal emits Deposit and GoalReached, later deposits only Deposit
+            let before = test::recorded_events().count();
+            piggy_bank.credit(accounts.alice, 600);
+            assert_eq!(test::recorded_events().count(), before + 2);
+            piggy_bank.credit(accounts.alice, 100);
+            assert_eq!(test::recorded_events().count(), before + 3);
+
+            // Raising the goal above the balance arms it again
+            piggy_bank.set_goal(1000).unwrap();
+            let before = test::recorded_events().count();
+            piggy_bank.credit(accounts.alice, 400);
+
+            let events = test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(events.len(), before + 2);
+            let reached = <GoalReached as ink::scale::Decode>::decode(&mut &events[events.len() - 1].data[..])
+                .expect("invalid GoalReached event data");
+            assert_eq!(reached.owner, accounts.alice);
+            assert_eq!(reached.goal, 1000);
+        }
+
+        #[ink::test]
+        fn require_goal_above_balance_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+            piggy_bank.balances.insert(accounts.alice, &500);
+
+            // Disabled by default
+            assert!(piggy_bank.set_goal(500).is_ok());
+
+            assert!(piggy_bank.set_require_goal_above_balance(true).is_ok());
+            assert!(piggy_bank.require_goal_above_balance());
+            assert_eq!(piggy_bank.set_goal(500), Err(Error::GoalNotReached));
+            assert_eq!(piggy_bank.set_goal(400), Err(Error::GoalNotReached));
+            assert!(piggy_bank.set_goal(501).is_ok());
+            assert_eq!(piggy_bank.goal_of(accounts.alice), 501);
+
+            assert!(piggy_bank.set_require_goal_above_balance(false).is_ok());
+            assert!(piggy_bank.set_goal(400).is_ok());
+
+            test::set_caller(get_bob());
+            assert_eq!(piggy_bank.set_require_goal_above_balance(true), Err(Error::Unauthorized));
+        }
+
+        #[ink::test]
+        fn import_position_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+            assert!(piggy_bank.import_open());
+
+            // The token pull is a cross-contract call, so seed the position directly
+            let bob = get_bob();
+            piggy_bank.import_into(bob, 700, 1000, 5000);
+            assert_eq!(piggy_bank.balance_of(bob), 700);
+            assert_eq!(piggy_bank.goal_of(bob), 1000);
+            assert_eq!(piggy_bank.lock_time_of(bob), 5000);
+            assert_eq!(piggy_bank.user_count(), 1);
+
+            assert!(piggy_bank.close_import().is_ok());
+            assert!(!piggy_bank.import_open());
+            assert_eq!(
+                piggy_bank.import_position(get_charlie(), 100, 0, 0),
+                Err(Error::ImportClosed)
+            );
+            assert_eq!(piggy_bank.balance_of(get_charlie()), 0);
+        }
+
+        #[ink::test]
+        fn import_cannot_overwrite_or_follow_deposits() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+            let bob = get_bob();
+
+            // Unbacked zero imports are rejected outright
+            assert_eq!(piggy_bank.import_position(bob, 0, 0, 0), Err(Error::ZeroAmount));
+
+            // An existing position is never overwritten
+            piggy_bank.import_into(bob, 700, 0, 0);
+            assert_eq!(piggy_bank.import_position(bob, 10, 0, 0), Err(Error::PositionExists));
+            assert_eq!(piggy_bank.balance_of(bob), 700);
+
+            // The first real deposit seals the import window
+            assert!(piggy_bank.import_open());
+            piggy_bank.credit(get_charlie(), 100);
+            assert!(!piggy_bank.import_open());
+            assert_eq!(piggy_bank.import_position(accounts.alice, 1_000_000, 0, 0), Err(Error::ImportClosed));
+            assert_eq!(piggy_bank.balance_of(accounts.alice), 0);
+        }
+
+        #[ink::test]
+        fn only_owner_can_import() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+
+            test::set_caller(get_bob());
+            assert_eq!(
+                piggy_bank.import_position(get_bob(), 100, 0, 0),
+                Err(Error::Unauthorized)
+            );
+            assert_eq!(piggy_bank.close_import(), Err(Error::Unauthorized));
+        }
+
+        #[ink::test]
+        fn deposit_overflow_fails() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+            piggy_bank.credit(accounts.alice, Balance::MAX);
+
+            // Rejected before any token is pulled
+            assert_eq!(piggy_bank.deposit(token_address, 1), Err(Error::Overflow));
+            assert_eq!(piggy_bank.balance_of(accounts.alice), Balance::MAX);
+        }
+
+        #[ink::test]
+        fn free_balance_is_withdrawable_while_locked() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+
+            // Same bookkeeping as deposit_split(700, 300)
+            piggy_bank.credit(accounts.alice, 1000);
+            piggy_bank.add_free_balance(accounts.alice, 300);
+            assert_eq!(piggy_bank.free_balance_of(accounts.alice), 300);
+            assert_eq!(piggy_bank.locked_balance_of(accounts.alice), 700);
+
+            set_timestamp(0);
+            assert!(piggy_bank.set_lock_time(1000).is_ok());
+            assert_eq!(piggy_bank.schedule_of(accounts.alice).withdrawable, 300);
+
+            // The free portion comes out while the lock is active
+            assert_eq!(piggy_bank.debit(accounts.alice, 200), Ok(800));
+            assert_eq!(piggy_bank.free_balance_of(accounts.alice), 100);
+
+            // Anything beyond it stays locked
+            assert_eq!(piggy_bank.debit(accounts.alice, 101), Err(Error::WithdrawalTooEarly));
+            assert_eq!(piggy_bank.balance_of(accounts.alice), 800);
+            assert_eq!(piggy_bank.locked_balance_of(accounts.alice), 700);
+
+            set_timestamp(1000);
+            assert_eq!(piggy_bank.debit(accounts.alice, 500), Ok(300));
+            assert_eq!(piggy_bank.free_balance_of(accounts.alice), 0);
+            assert_eq!(piggy_bank.locked_balance_of(accounts.alice), 300);
+        }
+
+        #[ink::test]
+        fn free_balance_never_exceeds_balance() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+
+            piggy_bank.credit(accounts.alice, 100);
+            piggy_bank.add_free_balance(accounts.alice, 500);
+            assert_eq!(piggy_bank.free_balance_of(accounts.alice), 100);
+
+            piggy_bank.set_balance(accounts.alice, 40);
+            assert_eq!(piggy_bank.free_balance_of(accounts.alice), 40);
+            assert_eq!(piggy_bank.locked_balance_of(accounts.alice), 0);
+        }
+
+        #[ink::test]
+        fn set_lock_time_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+
+            assert!(piggy_bank.set_lock_time(1000000).is_ok());
+            assert_eq!(piggy_bank.lock_time_of(accounts.alice), 1000000);
+        }
+
+        #[ink::test]
+        fn lock_block_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+
+            assert!(piggy_bank.set_lock_block(100).is_ok());
+            assert_eq!(piggy_bank.lock_block_of(accounts.alice), 100);
+            assert_eq!(piggy_bank.lock_block_of(get_bob()), 0);
+
+            set_block_number(99);
+            assert_eq!(piggy_bank.ensure_unlocked(accounts.alice), Err(Error::WithdrawalTooEarly));
+            assert!(!piggy_bank.can_withdraw(accounts.alice));
+
+            set_block_number(100);
+            assert!(piggy_bank.ensure_unlocked(accounts.alice).is_ok());
+        }
+
+        #[ink::test]
+        fn stricter_of_time_and_block_lock_applies() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+
+            assert!(piggy_bank.set_lock_time(1000).is_ok());
+            assert!(piggy_bank.set_lock_block(100).is_ok());
+
+            // Block lock passed, time lock still pending
+            set_block_number(100);
+            set_timestamp(999);
+            assert_eq!(piggy_bank.ensure_unlocked(accounts.alice), Err(Error::WithdrawalTooEarly));
+
+            // Time lock passed, block lock still pending
+            set_block_number(99);
+            set_timestamp(1000);
+            assert_eq!(piggy_bank.ensure_unlocked(accounts.alice), Err(Error::WithdrawalTooEarly));
+
+            set_block_number(100);
+            assert!(piggy_bank.ensure_unlocked(accounts.alice).is_ok());
+        }
+
+        #[ink::test]
+        fn lock_time_units_are_consistent() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+            piggy_bank.balances.insert(accounts.alice, &100);
+
+            piggy_bank.set_lock_time(5000).unwrap();
+            assert_eq!(piggy_bank.lock_time_ms(accounts.alice), 5000);
+            assert_eq!(piggy_bank.lock_time_secs(accounts.alice), 5);
+
+            // Partial seconds round up
+            piggy_bank.set_lock_time(5001).unwrap();
+            assert_eq!(piggy_bank.lock_time_secs(accounts.alice), 6);
+
+            // The lock check compares milliseconds with milliseconds
+            set_timestamp(5000);
+            assert_eq!(piggy_bank.withdraw(token_address, 10), Err(Error::WithdrawalTooEarly));
+            set_timestamp(5001);
+            assert!(piggy_bank.can_withdraw(accounts.alice));
+        }
+
+        #[ink::test]
+        fn goal_reached_logic_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+
+            piggy_bank.set_goal(100).unwrap();
+            assert!(!piggy_bank.is_goal_reached(accounts.alice));
+
+            // Manually set balance for testing
+            piggy_bank.balances.insert(accounts.alice, &100);
+            assert!(piggy_bank.is_goal_reached(accounts.alice));
+        }
+
+        #[ink::test]
+        fn multiple_users_work() {
+            let accounts = get_default_accounts();
+            let bob = get_bob();
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+
+            // Alice sets goal
+            test::set_caller(accounts.alice);
+            piggy_bank.set_goal(1000).unwrap();
+
+            // Bob sets different goal
+            test::set_caller(bob);
+            piggy_bank.set_goal(2000).unwrap();
+
+            assert_eq!(piggy_bank.goal_of(accounts.alice), 1000);
+            assert_eq!(piggy_bank.goal_of(bob), 2000);
+        }
+
+        #[ink::test]
+        fn schedule_of_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+
+            piggy_bank.balances.insert(accounts.alice, &500);
+            piggy_bank.set_goal(1000).unwrap();
+            piggy_bank.set_lock_time(2000).unwrap();
+
+            set_timestamp(1000);
+            let schedule = piggy_bank.schedule_of(accounts.alice);
+            assert_eq!(schedule.balance, 500);
+            assert_eq!(schedule.goal, 1000);
+            assert_eq!(schedule.lock_time, 2000);
+            assert_eq!(schedule.withdrawable, 0);
+
+            set_timestamp(2000);
+            assert_eq!(piggy_bank.schedule_of(accounts.alice).withdrawable, 500);
+        }
+
+        #[ink::test]
+        fn user_count_works() {
+            let accounts = get_default_accounts();
+            let bob = get_bob();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+            assert_eq!(piggy_bank.user_count(), 0);
+
+            piggy_bank.credit(accounts.alice, 100);
+            piggy_bank.credit(bob, 200);
+            piggy_bank.credit(bob, 50);
+            assert_eq!(piggy_bank.user_count(), 2);
+
+            // Alice withdraws everything
+            piggy_bank.set_balance(accounts.alice, 0);
+            assert_eq!(piggy_bank.user_count(), 1);
+            assert_eq!(piggy_bank.balance_of(bob), 250);
+        }
+
+        #[ink::test]
+        fn emergency_drain_timelock_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+            let bob = get_bob();
+
+            assert_eq!(piggy_bank.execute_emergency_drain(), Err(Error::NoDrainPending));
+
+            set_timestamp(1000);
+            assert!(piggy_bank.initiate_emergency_drain(bob).is_ok());
+            let executable_at = 1000 + EMERGENCY_DRAIN_DELAY;
+            assert_eq!(piggy_bank.pending_emergency_drain(), Some((bob, executable_at)));
+
+            let events = test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(events.len(), 1);
+
+            // Executing before the delay fails and keeps the drain pending
+            set_timestamp(executable_at - 1);
+            assert_eq!(piggy_bank.execute_emergency_drain(), Err(Error::DrainNotReady));
+            assert_eq!(piggy_bank.pending_emergency_drain(), Some((bob, executable_at)));
+        }
+
+        #[ink::test]
+        fn only_owner_can_drain() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+            let bob = get_bob();
+
+            test::set_caller(bob);
+            assert_eq!(piggy_bank.initiate_emergency_drain(bob), Err(Error::Unauthorized));
+            assert_eq!(piggy_bank.execute_emergency_drain(), Err(Error::Unauthorized));
+        }
+
+        #[ink::test]
+        fn suggested_goal_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+            assert_eq!(piggy_bank.suggested_goal(accounts.alice), 0);
+
+            piggy_bank.credit(accounts.alice, 100);
+            piggy_bank.credit(accounts.alice, 300);
+            piggy_bank.credit(accounts.alice, 200);
+
+            assert_eq!(piggy_bank.suggested_goal(accounts.alice), 600);
+            assert_eq!(piggy_bank.suggested_goal(get_bob()), 0);
+        }
+
+        #[ink::test]
+        fn release_mode_all_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+            piggy_bank.set_release_mode(ReleaseMode::All).unwrap();
+            piggy_bank.set_goal(100).unwrap();
+            piggy_bank.set_lock_time(1000).unwrap();
+            assert_eq!(piggy_bank.release_mode_of(accounts.alice), Some(ReleaseMode::All));
+
+            // Goal met but still time locked
+            piggy_bank.balances.insert(accounts.alice, &100);
+            assert!(!piggy_bank.can_withdraw(accounts.alice));
+            assert_eq!(piggy_bank.withdraw(token_address, 50), Err(Error::WithdrawalTooEarly));
+
+            // Time expired but goal unmet
+            set_timestamp(1000);
+            piggy_bank.balances.insert(accounts.alice, &99);
+            assert!(!piggy_bank.can_withdraw(accounts.alice));
+            assert_eq!(piggy_bank.withdraw(token_address, 50), Err(Error::GoalNotReached));
+
+            piggy_bank.balances.insert(accounts.alice, &100);
+            assert!(piggy_bank.can_withdraw(accounts.alice));
+        }
+
+        #[ink::test]
+        fn release_mode_any_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+            piggy_bank.set_release_mode(ReleaseMode::Any).unwrap();
+            piggy_bank.set_goal(100).unwrap();
+            piggy_bank.set_lock_time(1000).unwrap();
+
+            // Neither condition met
+            piggy_bank.balances.insert(accounts.alice, &99);
+            assert!(!piggy_bank.can_withdraw(accounts.alice));
+            assert_eq!(piggy_bank.withdraw(token_address, 50), Err(Error::WithdrawalTooEarly));
+
+            // Goal met but still time locked
+            piggy_bank.balances.insert(accounts.alice, &100);
+            assert!(piggy_bank.can_withdraw(accounts.alice));
+
+            // Time expired but goal unmet
+            set_timestamp(1000);
+            piggy_bank.balances.insert(accounts.alice, &99);
+            assert!(piggy_bank.can_withdraw(accounts.alice));
+        }
+
+        #[ink::test]
+        fn default_release_mode_uses_lock_time_only() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+            piggy_bank.set_goal(100).unwrap();
+            piggy_bank.set_lock_time(1000).unwrap();
+            piggy_bank.balances.insert(accounts.alice, &50);
+
+            assert_eq!(piggy_bank.release_mode_of(accounts.alice), None);
+            assert!(!piggy_bank.can_withdraw(accounts.alice));
+
+            set_timestamp(1000);
+            assert!(piggy_bank.can_withdraw(accounts.alice));
+        }
+
+        #[ink::test]
+        fn usd_goal_valuation_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+
+            // Goal of $150 with 2 tokens saved
+            piggy_bank.set_goal_usd(15_000).unwrap();
+            assert_eq!(piggy_bank.usd_goal_of(accounts.alice), 15_000);
+            let balance = 2 * PRICE_UNIT;
+
+            // At $50 per token the goal is unmet
+            let value = V6psp20piggybank::usd_value_of(balance, 5_000);
+            assert_eq!(value, 10_000);
+            assert!(value < piggy_bank.usd_goal_of(accounts.alice));
+
+            // At $100 per token the goal is met
+            let value = V6psp20piggybank::usd_value_of(balance, 10_000);
+            assert_eq!(value, 20_000);
+            assert!(value >= piggy_bank.usd_goal_of(accounts.alice));
+
+            piggy_bank.set_goal_usd(0).unwrap();
+            assert_eq!(piggy_bank.usd_goal_of(accounts.alice), 0);
+        }
+
+        #[ink::test]
+        fn usd_goal_without_oracle_uses_token_goal() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+            piggy_bank.set_goal(100).unwrap();
+            piggy_bank.set_goal_usd(15_000).unwrap();
+            piggy_bank.balances.insert(accounts.alice, &100);
+
+            assert_eq!(piggy_bank.price_oracle(), None);
+            assert!(piggy_bank.is_goal_reached(accounts.alice));
+
+            test::set_caller(get_bob());
+            assert_eq!(piggy_bank.set_price_oracle(get_charlie()), Err(Error::Unauthorized));
+        }
+
+        #[ink::test]
+        fn token_metadata_is_cached() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+
+            let metadata = (String::from("Piggy"), String::from("PIG"), 12);
+            piggy_bank.token_metadata = Some(metadata.clone());
+
+            // Served from the cache without calling the token
+            assert_eq!(piggy_bank.token_metadata(), metadata);
+        }
+
+        #[ink::test]
+        fn round_to_whole_works() {
+            assert_eq!(V6psp20piggybank::round_to_whole(1234, 2), 1200);
+            assert_eq!(V6psp20piggybank::round_to_whole(1200, 2), 1200);
+            assert_eq!(V6psp20piggybank::round_to_whole(99, 2), 0);
+            assert_eq!(V6psp20piggybank::round_to_whole(1234, 0), 1234);
+            assert_eq!(V6psp20piggybank::round_to_whole(Balance::MAX, 40), 0);
+        }
+
+        #[ink::test]
+        fn rounded_withdrawal_keeps_dust() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+            piggy_bank.token_metadata = Some((String::new(), String::new(), 2));
+            piggy_bank.balances.insert(accounts.alice, &250);
+
+            assert!(piggy_bank.set_round_withdrawals(true).is_ok());
+            assert!(piggy_bank.round_withdrawals());
+
+            // Less than one whole token rounds down to nothing and the balance is untouched
+            assert_eq!(piggy_bank.withdraw(token_address, 99), Err(Error::ZeroAmount));
+            assert_eq!(piggy_bank.balance_of(accounts.alice), 250);
+
+            test::set_caller(get_bob());
+            assert_eq!(piggy_bank.set_round_withdrawals(false), Err(Error::Unauthorized));
+        }
+
+        #[ink::test]
+        fn configure_many_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+            let bob = get_bob();
+            let charlie = get_charlie();
+
+            // Charlie already has a later lock that must not be shortened
+            test::set_caller(charlie);
+            piggy_bank.set_lock_time(9000).unwrap();
+
+            test::set_caller(accounts.alice);
+            assert!(piggy_bank.configure_many(vec![(bob, 1000, 5000), (charlie, 2000, 6000)]).is_ok());
+
+            assert_eq!(piggy_bank.goal_of(bob), 1000);
+            assert_eq!(piggy_bank.lock_time_of(bob), 5000);
+            assert_eq!(piggy_bank.goal_of(charlie), 2000);
+            assert_eq!(piggy_bank.lock_time_of(charlie), 9000);
+        }
+
+        #[ink::test]
+        fn configure_many_rejects_bad_calls() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+            let bob = get_bob();
+
+            let entries = vec![(bob, 1000, 5000); MAX_BATCH_SIZE + 1];
+            assert_eq!(piggy_bank.configure_many(entries), Err(Error::BatchTooLarge));
+
+            test::set_caller(bob);
+            assert_eq!(piggy_bank.configure_many(vec![(bob, 1000, 5000)]), Err(Error::Unauthorized));
+            assert_eq!(piggy_bank.goal_of(bob), 0);
+        }
+
+        #[ink::test]
+        fn position_nft_is_optional() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+            assert_eq!(piggy_bank.position_nft(), None);
+
+            // Without a position NFT contract nothing is minted or burned
+            piggy_bank.credit(accounts.alice, 100);
+            assert!(piggy_bank.mint_position(accounts.alice).is_ok());
+            assert!(piggy_bank.position_ids_of(accounts.alice).is_empty());
+
+            piggy_bank.set_balance(accounts.alice, 0);
+            assert!(piggy_bank.burn_positions_if_empty(accounts.alice).is_ok());
+
+            test::set_caller(get_bob());
+            assert_eq!(piggy_bank.set_position_nft(get_charlie()), Err(Error::Unauthorized));
+
+            // Minting goes through the NFT contract, so mark a receipt as issued directly
+            test::set_caller(accounts.alice);
+            assert!(piggy_bank.set_position_nft(get_charlie()).is_ok());
+            piggy_bank.next_position_id = 1;
+            assert_eq!(piggy_bank.set_position_nft(get_bob()), Err(Error::PositionsMinted));
+            assert_eq!(piggy_bank.position_nft(), Some(get_charlie()));
+        }
+
+        #[ink::test]
+        fn goals_completed_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+
+            piggy_bank.set_goal(100).unwrap();
+            piggy_bank.credit(accounts.alice, 60);
+            assert_eq!(piggy_bank.goals_completed_of(accounts.alice), 0);
+            piggy_bank.credit(accounts.alice, 40);
+            assert_eq!(piggy_bank.goals_completed_of(accounts.alice), 1);
+
+            // Deposits past an already completed goal do not count again
+            piggy_bank.credit(accounts.alice, 10);
+            assert_eq!(piggy_bank.goals_completed_of(accounts.alice), 1);
+
+            // Dropping below the goal and depositing back across it does not either
+            assert!(piggy_bank.debit(accounts.alice, 50).is_ok());
+            piggy_bank.credit(accounts.alice, 50);
+            assert_eq!(piggy_bank.goals_completed_of(accounts.alice), 1);
+
+            piggy_bank.set_goal(300).unwrap();
+            piggy_bank.credit(accounts.alice, 200);
+            assert_eq!(piggy_bank.goals_completed_of(accounts.alice), 2);
+            assert_eq!(piggy_bank.goals_completed_of(get_bob()), 0);
+        }
 
-        fn get_default_accounts() -> test::DefaultAccounts {
-            test::default_accounts()
+        #[ink::test]
+        fn goal_reached_at_records_first_time() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+
+            piggy_bank.set_goal(100).unwrap();
+            set_timestamp(1000);
+            piggy_bank.credit(accounts.alice, 60);
+            assert_eq!(piggy_bank.goal_reached_at(accounts.alice), None);
+
+            set_timestamp(2000);
+            piggy_bank.credit(accounts.alice, 40);
+            assert_eq!(piggy_bank.goal_reached_at(accounts.alice), Some(2000));
+
+            // Later deposits keep the original timestamp
+            set_timestamp(3000);
+            piggy_bank.credit(accounts.alice, 10);
+            assert_eq!(piggy_bank.goal_reached_at(accounts.alice), Some(2000));
+            assert_eq!(piggy_bank.goal_reached_at(get_bob()), None);
         }
 
-        fn get_bob() -> H160 {
-            H160::from([2u8; 20])
+        #[ink::test]
+        fn auto_withdraw_on_expiry_works() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+            let bob = get_bob();
+
+            piggy_bank.credit(accounts.alice, 500);
+            assert!(piggy_bank.set_lock_time(1000).is_ok());
+            assert_eq!(piggy_bank.debit_auto_withdraw(accounts.alice), Err(Error::AutoWithdrawDisabled));
+
+            assert!(piggy_bank.set_auto_withdraw_on_expiry(bob, true).is_ok());
+            assert_eq!(piggy_bank.auto_withdraw_of(accounts.alice), Some(bob));
+
+            // Before expiry
+            set_timestamp(999);
+            assert_eq!(piggy_bank.debit_auto_withdraw(accounts.alice), Err(Error::WithdrawalTooEarly));
+            assert_eq!(piggy_bank.balance_of(accounts.alice), 500);
+
+            // After expiry the whole balance goes to the designated address
+            set_timestamp(1000);
+            assert_eq!(piggy_bank.debit_auto_withdraw(accounts.alice), Ok((bob, 500)));
+            assert_eq!(piggy_bank.balance_of(accounts.alice), 0);
+            assert_eq!(piggy_bank.debit_auto_withdraw(accounts.alice), Err(Error::InsufficientBalance));
+
+            assert!(piggy_bank.set_auto_withdraw_on_expiry(bob, false).is_ok());
+            assert_eq!(piggy_bank.auto_withdraw_of(accounts.alice), None);
         }
 
-        fn create_mock_token() -> H160 {
-            // Create a mock token contract address for testing (H160 for ink! v6)
-            H160::from([0x01; 20])
+        #[ink::test]
+        fn auto_withdraw_waits_for_goal_in_all_mode() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+            let bob = get_bob();
+
+            piggy_bank.credit(accounts.alice, 500);
+            set_timestamp(0);
+            assert!(piggy_bank.set_release_mode(ReleaseMode::All).is_ok());
+            assert!(piggy_bank.set_goal(1000).is_ok());
+            assert!(piggy_bank.set_lock_time(1000).is_ok());
+            assert!(piggy_bank.set_auto_withdraw_on_expiry(bob, true).is_ok());
+
+            // The lock has expired but the goal has not been reached
+            set_timestamp(1000);
+            assert_eq!(piggy_bank.debit_auto_withdraw(accounts.alice), Err(Error::GoalNotReached));
+            assert_eq!(piggy_bank.balance_of(accounts.alice), 500);
+
+            piggy_bank.credit(accounts.alice, 500);
+            assert_eq!(piggy_bank.debit_auto_withdraw(accounts.alice), Ok((bob, 1000)));
         }
 
         #[ink::test]
-        fn new_works() {
+        fn projected_completion_uses_recent_rate() {
             let accounts = get_default_accounts();
             test::set_caller(accounts.alice);
 
             let token_address = create_mock_token();
-            let piggy_bank = V6psp20piggybank::new(token_address);
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+
+            // No goal yet
+            assert_eq!(piggy_bank.projected_completion(accounts.alice), None);
+            assert!(piggy_bank.set_goal(500).is_ok());
+
+            // A single deposit gives no rate
+            set_timestamp(0);
+            piggy_bank.credit(accounts.alice, 100);
+            assert_eq!(piggy_bank.projected_completion(accounts.alice), None);
+
+            // 200 saved over 2000ms leaves 200 to go, another 2000ms
+            set_timestamp(1000);
+            piggy_bank.credit(accounts.alice, 100);
+            set_timestamp(2000);
+            piggy_bank.credit(accounts.alice, 100);
+            assert_eq!(piggy_bank.projected_completion(accounts.alice), Some(4000));
+
+            // Only the most recent deposits count toward the rate
+            for i in 0..RECENT_DEPOSITS as u64 {
+                set_timestamp(3000 + i * 1000);
+                piggy_bank.credit(accounts.alice, 10);
+            }
+            let balance = piggy_bank.balance_of(accounts.alice);
+            let now = 3000 + (RECENT_DEPOSITS as u64 - 1) * 1000;
+            let saved = 10 * (RECENT_DEPOSITS as u128 - 1);
+            let span = (RECENT_DEPOSITS as u64 - 1) * 1000;
+            let expected = now + ((500 - balance) * span as u128 / saved) as u64;
+            assert_eq!(piggy_bank.projected_completion(accounts.alice), Some(expected));
+
+            // Nothing to project once the goal is met
+            piggy_bank.credit(accounts.alice, 500);
+            assert_eq!(piggy_bank.projected_completion(accounts.alice), None);
+        }
+
+        #[ink::test]
+        fn stats_aggregate_all_users() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+            let bob = get_bob();
+
+            piggy_bank.credit(accounts.alice, 300);
+            piggy_bank.credit(bob, 500);
+            assert_eq!(piggy_bank.debit(accounts.alice, 100), Ok(200));
+            assert_eq!(piggy_bank.debit(bob, 500), Ok(0));
+            piggy_bank.credit(bob, 50);
+
+            // Joint jars count towards the primary token's value locked, other tokens are totalled apart
+            let jar_id = piggy_bank.create_joint_jar(vec![bob]).unwrap();
+            piggy_bank.credit_jar(jar_id, bob, 400);
+            assert_eq!(piggy_bank.debit_jar(jar_id, bob, 100), Ok(300));
+            let other_token = H160::from([7u8; 20]);
+            piggy_bank.credit_token(accounts.alice, other_token, 900);
+            piggy_bank.credit_token(bob, other_token, 100);
+            assert_eq!(piggy_bank.debit_token(bob, other_token, 40), Ok(60));
+            assert_eq!(piggy_bank.token_value_locked(other_token), 960);
+
+            assert_eq!(
+                piggy_bank.stats(),
+                Stats {
+                    total_value_locked: 550,
+                    user_count: 2,
+                    total_deposited_lifetime: 850,
+                    total_withdrawn_lifetime: 600,
+                }
+            );
+        }
+
+        #[ink::test]
+        fn lifetime_stats_accumulate() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+            assert_eq!(piggy_bank.lifetime_stats(accounts.alice), (0, 0));
+
+            piggy_bank.credit(accounts.alice, 300);
+            piggy_bank.credit(accounts.alice, 200);
+            assert_eq!(piggy_bank.debit(accounts.alice, 100), Ok(400));
+            piggy_bank.credit(accounts.alice, 50);
+            assert_eq!(piggy_bank.debit(accounts.alice, 150), Ok(300));
+
+            assert_eq!(piggy_bank.lifetime_stats(accounts.alice), (550, 250));
+            assert_eq!(piggy_bank.balance_of(accounts.alice), 300);
+
+            // Failed withdrawals are not counted
+            assert_eq!(piggy_bank.debit(accounts.alice, 1000), Err(Error::InsufficientBalance));
+            assert_eq!(piggy_bank.lifetime_stats(accounts.alice), (550, 250));
+            assert_eq!(piggy_bank.lifetime_stats(get_bob()), (0, 0));
+        }
+
+        #[ink::test]
+        fn withdrawals_cannot_leave_dust() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+            assert!(piggy_bank.set_min_remaining_balance(100).is_ok());
+            assert_eq!(piggy_bank.min_remaining_balance(), 100);
+            piggy_bank.credit(accounts.alice, 500);
+
+            // Leaving at least the minimum
+            assert_eq!(piggy_bank.debit(accounts.alice, 400), Ok(100));
+
+            // Leaving dust
+            assert_eq!(piggy_bank.debit(accounts.alice, 50), Err(Error::DustNotAllowed));
+            assert_eq!(piggy_bank.balance_of(accounts.alice), 100);
+
+            // Emptying the position
+            assert_eq!(piggy_bank.debit(accounts.alice, 100), Ok(0));
+
+            test::set_caller(get_bob());
+            assert_eq!(piggy_bank.set_min_remaining_balance(0), Err(Error::Unauthorized));
+        }
+
+        #[ink::test]
+        fn max_single_withdrawal_caps_withdrawals() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+            piggy_bank.credit(accounts.alice, 1000);
+
+            assert_eq!(piggy_bank.max_single_withdrawal(), None);
+            assert!(piggy_bank.ensure_within_max_withdrawal(1000).is_ok());
+
+            assert!(piggy_bank.set_max_single_withdrawal(Some(300)).is_ok());
+            assert_eq!(piggy_bank.max_single_withdrawal(), Some(300));
+
+            // At the limit passes, over it is rejected before any balance moves
+            assert!(piggy_bank.ensure_within_max_withdrawal(300).is_ok());
+            assert_eq!(piggy_bank.withdraw(token_address, 301), Err(Error::ExceedsMaxWithdrawal));
+            assert_eq!(piggy_bank.withdraw_to(get_charlie(), 301), Err(Error::ExceedsMaxWithdrawal));
+            assert_eq!(
+                piggy_bank.withdraw_split(301, vec![(accounts.alice, BASIS_POINTS)]),
+                Err(Error::ExceedsMaxWithdrawal)
+            );
+            assert_eq!(piggy_bank.balance_of(accounts.alice), 1000);
+
+            test::set_caller(get_bob());
+            assert_eq!(piggy_bank.set_max_single_withdrawal(None), Err(Error::Unauthorized));
+        }
+
+        #[ink::test]
+        fn deposit_for_rejects_bad_input() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+            let bob = get_bob();
+
+            assert_eq!(piggy_bank.deposit_for(bob, 0), Err(Error::ZeroAmount));
+            assert_eq!(piggy_bank.deposit_for(H160::zero(), 100), Err(Error::ZeroAddress));
 
+            // The overflow pre-check runs against the beneficiary's balance
+            piggy_bank.credit(bob, Balance::MAX);
+            assert_eq!(piggy_bank.deposit_for(bob, 1), Err(Error::Overflow));
             assert_eq!(piggy_bank.balance_of(accounts.alice), 0);
-            assert_eq!(piggy_bank.owner(), accounts.alice);
         }
 
         #[ink::test]
-        fn set_goal_works() {
+        fn withdraw_to_checks_the_caller() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+            let cold_wallet = get_charlie();
+
+            assert_eq!(piggy_bank.withdraw_to(H160::zero(), 100), Err(Error::ZeroAddress));
+
+            // Balance and lock checks run against the caller, not the recipient
+            piggy_bank.credit(cold_wallet, 500);
+            assert_eq!(piggy_bank.withdraw_to(cold_wallet, 100), Err(Error::InsufficientBalance));
+
+            piggy_bank.credit(accounts.alice, 500);
+            set_timestamp(0);
+            assert!(piggy_bank.set_lock_time(1000).is_ok());
+            assert_eq!(piggy_bank.withdraw_to(cold_wallet, 100), Err(Error::WithdrawalTooEarly));
+            assert_eq!(piggy_bank.balance_of(accounts.alice), 500);
+            assert_eq!(piggy_bank.balance_of(cold_wallet), 500);
+        }
+
+        #[ink::test]
+        fn tokens_are_saved_independently() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+            let token_a = H160::from([0xAA; 20]);
+            let token_b = H160::from([0xBB; 20]);
+
+            piggy_bank.credit_token(accounts.alice, token_a, 300);
+            piggy_bank.credit_token(accounts.alice, token_b, 500);
+            piggy_bank.credit(accounts.alice, 700);
+            assert_eq!(piggy_bank.balance_of_token(accounts.alice, token_a), 300);
+            assert_eq!(piggy_bank.balance_of_token(accounts.alice, token_b), 500);
+            assert_eq!(piggy_bank.balance_of_token(accounts.alice, token_address), 700);
+
+            // Goals and locks are kept per token
+            assert!(piggy_bank.set_token_goal(token_a, 1000).is_ok());
+            set_timestamp(0);
+            assert!(piggy_bank.set_token_lock_time(token_b, 1000).is_ok());
+            assert_eq!(piggy_bank.goal_of_token(accounts.alice, token_a), 1000);
+            assert_eq!(piggy_bank.goal_of_token(accounts.alice, token_b), 0);
+            assert_eq!(piggy_bank.lock_time_of_token(accounts.alice, token_b), 1000);
+            assert_eq!(piggy_bank.lock_time_of(accounts.alice), 0);
+
+            // Token A is held back until its own goal is reached
+            assert_eq!(piggy_bank.debit_token(accounts.alice, token_a, 100), Err(Error::GoalNotReached));
+            piggy_bank.credit_token(accounts.alice, token_a, 700);
+
+            // Withdrawing token A leaves token B and the primary token untouched
+            assert_eq!(piggy_bank.debit_token(accounts.alice, token_a, 100), Ok(900));
+            assert_eq!(piggy_bank.balance_of_token(accounts.alice, token_b), 500);
+            assert_eq!(piggy_bank.balance_of(accounts.alice), 700);
+
+            assert_eq!(piggy_bank.debit_token(accounts.alice, token_b, 100), Err(Error::WithdrawalTooEarly));
+            assert_eq!(piggy_bank.debit_token(accounts.alice, token_a, 901), Err(Error::InsufficientBalance));
+
+            set_timestamp(1000);
+            assert_eq!(piggy_bank.debit_token(accounts.alice, token_b, 500), Ok(0));
+            assert_eq!(piggy_bank.balance_of_token(accounts.alice, token_b), 0);
+            assert_eq!(piggy_bank.balance_of_token(accounts.alice, token_a), 900);
+        }
+
+        #[ink::test]
+        fn joint_jar_members_share_balance() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+            let bob = get_bob();
+            let charlie = get_charlie();
+
+            let jar_id = piggy_bank.create_joint_jar(vec![bob, accounts.alice]).unwrap();
+            assert_eq!(piggy_bank.jar_members(jar_id), vec![accounts.alice, bob]);
+            assert_eq!(piggy_bank.jar_owner(jar_id), Some(accounts.alice));
+
+            piggy_bank.credit_jar(jar_id, accounts.alice, 500);
+            assert_eq!(piggy_bank.jar_balance(jar_id), 500);
+
+            // Both members can withdraw from the shared balance
+            assert_eq!(piggy_bank.debit_jar(jar_id, bob, 200), Ok(300));
+            assert_eq!(piggy_bank.debit_jar(jar_id, accounts.alice, 100), Ok(200));
+            assert_eq!(piggy_bank.debit_jar(jar_id, bob, 300), Err(Error::InsufficientBalance));
+
+            // Non-members are rejected, and individual balances are untouched
+            assert_eq!(piggy_bank.debit_jar(jar_id, charlie, 1), Err(Error::NotJarMember));
+            test::set_caller(charlie);
+            assert_eq!(piggy_bank.deposit_to_jar(jar_id, 10), Err(Error::NotJarMember));
+            assert_eq!(piggy_bank.withdraw_from_jar(jar_id, 10), Err(Error::NotJarMember));
+            assert_eq!(piggy_bank.balance_of(bob), 0);
+
+            assert_eq!(piggy_bank.debit_jar(7, bob, 1), Err(Error::JarNotFound));
+        }
+
+        #[ink::test]
+        fn joint_jar_membership_is_managed_by_creator() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+            let bob = get_bob();
+            let charlie = get_charlie();
+
+            let jar_id = piggy_bank.create_joint_jar(vec![bob]).unwrap();
+            assert_eq!(piggy_bank.create_joint_jar(Vec::new()), Ok(jar_id + 1));
+
+            assert!(piggy_bank.add_jar_member(jar_id, charlie).is_ok());
+            assert_eq!(piggy_bank.jar_members(jar_id), vec![accounts.alice, bob, charlie]);
+
+            assert!(piggy_bank.remove_jar_member(jar_id, bob).is_ok());
+            assert_eq!(piggy_bank.jar_members(jar_id), vec![accounts.alice, charlie]);
+            assert_eq!(piggy_bank.remove_jar_member(jar_id, accounts.alice), Err(Error::Unauthorized));
+
+            piggy_bank.credit_jar(jar_id, accounts.alice, 100);
+            assert_eq!(piggy_bank.debit_jar(jar_id, bob, 10), Err(Error::NotJarMember));
+
+            test::set_caller(charlie);
+            assert_eq!(piggy_bank.add_jar_member(jar_id, bob), Err(Error::Unauthorized));
+            assert_eq!(piggy_bank.remove_jar_member(jar_id, accounts.alice), Err(Error::Unauthorized));
+            assert_eq!(piggy_bank.add_jar_member(9, bob), Err(Error::JarNotFound));
+        }
+
+        #[ink::test]
+        fn early_unlock_clears_lock() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+            piggy_bank.credit(accounts.alice, 500);
+
+            set_timestamp(0);
+            assert!(piggy_bank.set_lock_time(1000).is_ok());
+            assert!(piggy_bank.set_lock_block(100).is_ok());
+            assert!(!piggy_bank.can_withdraw(accounts.alice));
+
+            piggy_bank.clear_lock(accounts.alice);
+            assert_eq!(piggy_bank.lock_time_of(accounts.alice), 0);
+            assert_eq!(piggy_bank.lock_block_of(accounts.alice), 0);
+            assert_eq!(piggy_bank.debit(accounts.alice, 500), Ok(0));
+        }
+
+        #[ink::test]
+        fn locks_cannot_be_shortened_for_free() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+            piggy_bank.credit(accounts.alice, 500);
+            set_timestamp(0);
+            set_block_number(0);
+
+            assert!(piggy_bank.set_lock_time(1000).is_ok());
+            assert!(piggy_bank.set_lock_block(100).is_ok());
+
+            // Shorter locks are ignored
+            assert!(piggy_bank.set_lock_time(0).is_ok());
+            assert!(piggy_bank.set_lock_block(0).is_ok());
+            assert_eq!(piggy_bank.lock_time_of(accounts.alice), 1000);
+            assert_eq!(piggy_bank.lock_block_of(accounts.alice), 100);
+            assert_eq!(piggy_bank.debit(accounts.alice, 100), Err(Error::WithdrawalTooEarly));
+
+            // Nor can the goal route open the lock early
+            assert_eq!(piggy_bank.set_release_mode(ReleaseMode::Any), Err(Error::WithdrawalTooEarly));
+            assert!(piggy_bank.set_release_mode(ReleaseMode::All).is_ok());
+            assert!(piggy_bank.set_goal(0).is_ok());
+            assert_eq!(piggy_bank.debit(accounts.alice, 100), Err(Error::WithdrawalTooEarly));
+
+            // Longer locks still apply
+            assert!(piggy_bank.set_lock_time(2000).is_ok());
+            assert_eq!(piggy_bank.lock_time_of(accounts.alice), 2000);
+        }
+
+        #[ink::test]
+        fn goals_cannot_be_lowered_while_locked_in_any_mode() {
             let accounts = get_default_accounts();
             test::set_caller(accounts.alice);
 
             let token_address = create_mock_token();
             let mut piggy_bank = V6psp20piggybank::new(token_address);
+            piggy_bank.credit(accounts.alice, 500);
+            set_timestamp(0);
 
+            assert!(piggy_bank.set_release_mode(ReleaseMode::Any).is_ok());
             assert!(piggy_bank.set_goal(1000).is_ok());
-            assert_eq!(piggy_bank.goal_of(accounts.alice), 1000);
+            assert!(piggy_bank.set_lock_time(1000).is_ok());
+
+            assert_eq!(piggy_bank.set_goal(0), Err(Error::WithdrawalTooEarly));
+            assert_eq!(piggy_bank.set_goal_usd(1), Err(Error::WithdrawalTooEarly));
+            assert!(piggy_bank.set_goal(2000).is_ok());
+            assert!(!piggy_bank.can_withdraw(accounts.alice));
+
+            set_timestamp(1000);
+            assert!(piggy_bank.set_goal(0).is_ok());
         }
 
         #[ink::test]
-        fn set_lock_time_works() {
+        fn first_goal_cannot_release_a_lock_in_any_mode() {
             let accounts = get_default_accounts();
             test::set_caller(accounts.alice);
 
             let token_address = create_mock_token();
             let mut piggy_bank = V6psp20piggybank::new(token_address);
+            piggy_bank.credit(accounts.alice, 500);
+            set_timestamp(0);
+
+            assert!(piggy_bank.set_release_mode(ReleaseMode::Any).is_ok());
+            assert!(piggy_bank.set_lock_time(1000).is_ok());
+
+            // With no goal yet, setting one the balance already covers would unlock for free
+            assert_eq!(piggy_bank.set_goal(500), Err(Error::WithdrawalTooEarly));
+            assert_eq!(piggy_bank.set_goal(600), Err(Error::WithdrawalTooEarly));
+            assert_eq!(piggy_bank.goal_of(accounts.alice), 0);
+            assert!(!piggy_bank.can_withdraw(accounts.alice));
+
+            // Once a goal exists, raising it above the balance is still allowed
+            set_timestamp(1000);
+            assert!(piggy_bank.set_goal(600).is_ok());
+            assert!(piggy_bank.set_lock_time(2000).is_ok());
+            assert!(piggy_bank.set_goal(700).is_ok());
+            assert_eq!(piggy_bank.set_goal(400), Err(Error::WithdrawalTooEarly));
+            assert!(!piggy_bank.can_withdraw(accounts.alice));
+        }
 
-            assert!(piggy_bank.set_lock_time(1000000).is_ok());
-            assert_eq!(piggy_bank.lock_time_of(accounts.alice), 1000000);
+        #[ink::test]
+        fn early_unlock_fee_settings() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+
+            // Disabled by default
+            assert_eq!(piggy_bank.buy_early_unlock(), Err(Error::EarlyUnlockDisabled));
+
+            assert!(piggy_bank.set_early_unlock_fee(25).is_ok());
+            assert_eq!(piggy_bank.early_unlock_fee(), 25);
+
+            // Not locked, so nothing to buy and no token call is made
+            assert!(piggy_bank.buy_early_unlock().is_ok());
+
+            test::set_caller(get_bob());
+            assert_eq!(piggy_bank.set_early_unlock_fee(0), Err(Error::Unauthorized));
         }
 
         #[ink::test]
-        fn goal_reached_logic_works() {
+        fn contract_address_is_env_address() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let piggy_bank = V6psp20piggybank::new(token_address);
+
+            // The address pulled into and queried on the token is the contract's own H160
+            let callee = H160::from([9u8; 20]);
+            test::set_callee(callee);
+            assert_eq!(piggy_bank.contract_address(), callee);
+            assert_eq!(piggy_bank.contract_address(), ink::env::address());
+        }
+
+        #[ink::test]
+        fn extend_lock_never_shortens() {
             let accounts = get_default_accounts();
             test::set_caller(accounts.alice);
 
             let token_address = create_mock_token();
             let mut piggy_bank = V6psp20piggybank::new(token_address);
 
-            piggy_bank.set_goal(100).unwrap();
-            assert!(!piggy_bank.is_goal_reached(accounts.alice));
+            piggy_bank.extend_lock(accounts.alice, 5000);
+            assert_eq!(piggy_bank.lock_time_of(accounts.alice), 5000);
 
-            // Manually set balance for testing
-            piggy_bank.balances.insert(accounts.alice, &100);
-            assert!(piggy_bank.is_goal_reached(accounts.alice));
+            piggy_bank.extend_lock(accounts.alice, 3000);
+            assert_eq!(piggy_bank.lock_time_of(accounts.alice), 5000);
+
+            // Both calls report the effective lock time
+            let events = test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(events.len(), 2);
+            for event in events {
+                let decoded = <LockTimeSet as ink::scale::Decode>::decode(&mut &event.data[..])
+                    .expect("invalid LockTimeSet event data");
+                assert_eq!(decoded.owner, accounts.alice);
+                assert_eq!(decoded.lock_time, 5000);
+            }
         }
 
         #[ink::test]
-        fn multiple_users_work() {
+        fn pool_balances_track_funding() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+            assert_eq!(piggy_bank.pool_balances(), (0, 0, 0));
+
+            piggy_bank.credit_pool(Pool::Interest, 100);
+            piggy_bank.credit_pool(Pool::Match, 200);
+            piggy_bank.credit_pool(Pool::Bonus, 300);
+            piggy_bank.credit_pool(Pool::Interest, 50);
+            assert_eq!(piggy_bank.pool_balances(), (150, 200, 300));
+        }
+
+        #[ink::test]
+        fn fund_pool_rejects_bad_calls() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+
+            assert_eq!(piggy_bank.fund_interest_pool(0), Err(Error::ZeroAmount));
+
+            test::set_caller(get_bob());
+            assert_eq!(piggy_bank.fund_interest_pool(100), Err(Error::Unauthorized));
+            assert_eq!(piggy_bank.fund_match_pool(100), Err(Error::Unauthorized));
+            assert_eq!(piggy_bank.fund_bonus_pool(100), Err(Error::Unauthorized));
+            assert_eq!(piggy_bank.pool_balances(), (0, 0, 0));
+        }
+
+        #[ink::test]
+        fn only_owner_can_probe_token_fee() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+            assert!(!piggy_bank.token_has_fee());
+
+            test::set_caller(get_bob());
+            assert_eq!(piggy_bank.probe_token_fee(100), Err(Error::Unauthorized));
+        }
+
+        #[ink::test]
+        fn probe_token_fee_runs_once() {
             let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
+
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+            piggy_bank.fee_probed = true;
+
+            assert_eq!(piggy_bank.probe_token_fee(100), Err(Error::FeeAlreadyProbed));
+        }
+
+        #[ink::test]
+        fn split_amounts_works() {
             let bob = get_bob();
+            let charlie = get_charlie();
+
+            let shares = V6psp20piggybank::split_amounts(1000, &[(bob, 2500), (charlie, 7500)]).unwrap();
+            assert_eq!(shares, vec![(bob, 250), (charlie, 750)]);
+
+            // Rounding dust goes to the last recipient
+            let shares = V6psp20piggybank::split_amounts(1001, &[(bob, 5000), (charlie, 5000)]).unwrap();
+            assert_eq!(shares, vec![(bob, 500), (charlie, 501)]);
+        }
+
+        #[ink::test]
+        fn withdraw_split_bad_sum_fails() {
+            let accounts = get_default_accounts();
+            test::set_caller(accounts.alice);
 
             let token_address = create_mock_token();
             let mut piggy_bank = V6psp20piggybank::new(token_address);
+            piggy_bank.balances.insert(accounts.alice, &1000);
 
-            // Alice sets goal
+            let result = piggy_bank.withdraw_split(500, vec![(get_bob(), 5000), (get_charlie(), 4000)]);
+            assert_eq!(result, Err(Error::InvalidSplit));
+            assert_eq!(piggy_bank.balance_of(accounts.alice), 1000);
+        }
+
+        #[ink::test]
+        fn withdraw_split_respects_lock_time() {
+            let accounts = get_default_accounts();
             test::set_caller(accounts.alice);
-            piggy_bank.set_goal(1000).unwrap();
 
-            // Bob sets different goal
-            test::set_caller(bob);
-            piggy_bank.set_goal(2000).unwrap();
+            let token_address = create_mock_token();
+            let mut piggy_bank = V6psp20piggybank::new(token_address);
+            piggy_bank.balances.insert(accounts.alice, &1000);
+            piggy_bank.set_lock_time(1000000).unwrap();
 
-            assert_eq!(piggy_bank.goal_of(accounts.alice), 1000);
-            assert_eq!(piggy_bank.goal_of(bob), 2000);
+            let result = piggy_bank.withdraw_split(500, vec![(get_bob(), 5000), (get_charlie(), 5000)]);
+            assert_eq!(result, Err(Error::WithdrawalTooEarly));
         }
     }
 